@@ -0,0 +1,280 @@
+//! 复权连续合约：在主力合约序列的每次换月处，用价差或者比例消除新旧合约之间的跳空。
+use super::{
+    main_contract::{self, MainSeries},
+    Target,
+};
+use crate::{
+    ce::{self, Bar},
+    Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::Date;
+
+/// 复权方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// 价差：调整后价格 = 原价格 + factor
+    Diff,
+    /// 比例：调整后价格 = 原价格 × factor
+    #[default]
+    Ratio,
+}
+
+/// 复权方向
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// 前复权：最新价格不变，调整换月之前的价格
+    #[default]
+    Backward,
+    /// 后复权：最早价格不变，调整换月之后的价格
+    Forward,
+}
+
+impl std::str::FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "diff" => Method::Diff,
+            "ratio" => Method::Ratio,
+            _ => return Err(format!("{s} 不是复权方式，只支持 diff/ratio")),
+        })
+    }
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "backward" => Direction::Backward,
+            "forward" => Direction::Forward,
+            _ => return Err(format!("{s} 不是复权方向，只支持 backward/forward")),
+        })
+    }
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Diff => "diff",
+            Method::Ratio => "ratio",
+        }
+    }
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Backward => "backward",
+            Direction::Forward => "forward",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Adjust {
+    pub method: Method,
+    pub direction: Direction,
+}
+
+/// 复权后的一天：与 `qihuo.ce_continuous` 的列一一对应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContinuousBar {
+    pub date: Date,
+    pub product: Str,
+    /// 当日的主力合约
+    pub code: Str,
+    pub open: f32,
+    pub high: f32,
+    pub low: f32,
+    pub close: f32,
+    pub settle: f32,
+    /// 主力合约的成交量、交易额、持仓量，不复权
+    pub vol: u32,
+    pub amount: f32,
+    pub position: u32,
+    /// 当日使用的复权因子：见 [`Method`]
+    pub factor: f64,
+    pub method: Method,
+    pub direction: Direction,
+}
+
+/// 根据主力合约序列生成复权连续合约。`bars` 为各合约的日线，用于查找新合约在换月前一天的收盘价；
+/// 找不到时该次换月不做调整。
+pub fn continuous(bars: &[Bar], main: &MainSeries, adjust: Adjust) -> Vec<ContinuousBar> {
+    let close: HashMap<(&str, Date), f32> = bars
+        .iter()
+        .map(|b| ((b.code.as_str(), b.date), b.close))
+        .collect();
+    let Adjust { method, direction } = adjust;
+    let neutral = match method {
+        Method::Diff => 0.0,
+        Method::Ratio => 1.0,
+    };
+    // 每条记录相对于前一条记录的跳空：只在换月当天不为 neutral
+    let gaps: Vec<f64> = main
+        .bars
+        .iter()
+        .enumerate()
+        .map(|(i, bar)| {
+            let Some(prev) = i.checked_sub(1).map(|j| &main.bars[j]) else {
+                return neutral;
+            };
+            if !bar.roll || prev.product != bar.product {
+                return neutral;
+            }
+            let (old, new) = (
+                prev.close as f64,
+                close.get(&(bar.code.as_str(), prev.date)),
+            );
+            match (new, method) {
+                (Some(&new), Method::Diff) => new as f64 - old,
+                (Some(&new), Method::Ratio) if old != 0.0 && new != 0.0 => new as f64 / old,
+                _ => {
+                    warn!(
+                        "{} {} 换月到 {} 时找不到 {} 的有效收盘价，不做调整",
+                        bar.date, bar.product, bar.code, prev.date
+                    );
+                    neutral
+                }
+            }
+        })
+        .collect();
+    let combine = |acc: f64, gap: f64| match method {
+        Method::Diff => acc + gap,
+        Method::Ratio => acc * gap,
+    };
+    let mut factors = vec![neutral; gaps.len()];
+    match direction {
+        Direction::Backward => {
+            // 从最新往前：换月之前的记录累积之后所有的跳空
+            let mut acc = neutral;
+            for i in (0..gaps.len()).rev() {
+                factors[i] = acc;
+                let new_product = i == 0 || main.bars[i - 1].product != main.bars[i].product;
+                acc = if new_product {
+                    neutral
+                } else {
+                    combine(acc, gaps[i])
+                };
+            }
+        }
+        Direction::Forward => {
+            // 从最早往后：换月之后的记录抵消之前所有的跳空
+            let mut acc = neutral;
+            for i in 0..gaps.len() {
+                let new_product = i == 0 || main.bars[i - 1].product != main.bars[i].product;
+                acc = if new_product {
+                    neutral
+                } else {
+                    combine(acc, gaps[i])
+                };
+                factors[i] = match method {
+                    Method::Diff => -acc,
+                    Method::Ratio => 1.0 / acc,
+                };
+            }
+        }
+    }
+    main.bars
+        .iter()
+        .zip(factors)
+        .map(|(bar, factor)| {
+            let adj = |price: f32| match method {
+                Method::Diff => (price as f64 + factor) as f32,
+                Method::Ratio => (price as f64 * factor) as f32,
+            };
+            ContinuousBar {
+                date: bar.date,
+                product: bar.product.clone(),
+                code: bar.code.clone(),
+                open: adj(bar.open),
+                high: adj(bar.high),
+                low: adj(bar.low),
+                close: adj(bar.close),
+                settle: adj(bar.settle),
+                vol: bar.vol,
+                amount: bar.amount,
+                position: bar.position,
+                factor,
+                method,
+                direction,
+            }
+        })
+        .collect()
+}
+
+/// 从 `qihuo.ce` 读取数据，生成主力合约序列和复权连续合约，并保存到 `targets`
+pub fn run(
+    selection: &ce::Selection,
+    opts: &main_contract::Options,
+    adjust: Adjust,
+    targets: &[Target],
+) -> Result<Vec<ContinuousBar>> {
+    let bars = ce::load(selection)?;
+    let main = main_contract::main_contract(&bars, opts);
+    let data = continuous(&bars, &main, adjust);
+    let products = super::products(data.iter().map(|b| b.product.as_str()));
+    let condition = format!(
+        " AND method = '{}' AND direction = '{}'{}",
+        adjust.method.as_str(),
+        adjust.direction.as_str(),
+        super::date_condition(selection),
+    );
+    super::save(
+        &data,
+        &products,
+        "continuous",
+        &condition,
+        targets,
+        |fname| save_parquet(&data, fname),
+    )?;
+    Ok(data)
+}
+
+#[cfg(feature = "parquet")]
+fn save_parquet(data: &[ContinuousBar], fname: &str) -> Result<()> {
+    use crate::util::parquet::{save_parquet, Column};
+    const SCHEMA: &str = "message ce_continuous {
+        required int32 date (DATE);
+        required binary product (UTF8);
+        required binary code (UTF8);
+        required float open;
+        required float high;
+        required float low;
+        required float close;
+        required float settle;
+        required int64 vol;
+        required float amount;
+        required int64 position;
+        required double factor;
+        required binary method (UTF8);
+        required binary direction (UTF8);
+    }";
+    let float = |f: fn(&ContinuousBar) -> f32| Column::Float(data.iter().map(f).collect());
+    let columns = vec![
+        Column::date(data.iter().map(|b| b.date)),
+        Column::str(data.iter().map(|b| b.product.as_str())),
+        Column::str(data.iter().map(|b| b.code.as_str())),
+        float(|b| b.open),
+        float(|b| b.high),
+        float(|b| b.low),
+        float(|b| b.close),
+        float(|b| b.settle),
+        Column::Long(data.iter().map(|b| b.vol as i64).collect()),
+        float(|b| b.amount),
+        Column::Long(data.iter().map(|b| b.position as i64).collect()),
+        Column::Double(data.iter().map(|b| b.factor).collect()),
+        Column::str(data.iter().map(|b| b.method.as_str())),
+        Column::str(data.iter().map(|b| b.direction.as_str())),
+    ];
+    save_parquet(SCHEMA, columns, fname)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+use super::no_parquet as save_parquet;
@@ -0,0 +1,156 @@
+//! 期限结构：某个交易日品种各合约按交割月排列的结算价，以及近月、次近月合约的价差历史。
+use crate::{
+    ce::{self, Bar},
+    util, Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::Date;
+
+/// 曲线上的一个合约
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub code: Str,
+    /// 交割年月
+    pub delivery: (i32, u8),
+    pub settle: f32,
+    pub close: f32,
+    pub position: u32,
+    /// 与前一个（交割月更近的）合约的结算价之差；第一个合约为 0
+    pub spread: f32,
+}
+
+/// 曲线形态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    /// 远月高于近月（正向市场）
+    Contango,
+    /// 远月低于近月（反向市场）
+    Backwardation,
+    /// 远月等于近月，或者只有一个合约
+    Flat,
+}
+
+impl Shape {
+    fn of(spread: f32) -> Shape {
+        if spread > 0.0 {
+            Shape::Contango
+        } else if spread < 0.0 {
+            Shape::Backwardation
+        } else {
+            Shape::Flat
+        }
+    }
+}
+
+/// 某个交易日的期限结构
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    pub date: Date,
+    pub product: Str,
+    /// 按交割月从近到远排列
+    pub points: Vec<Point>,
+    /// 最远月与最近月的比较
+    pub shape: Shape,
+}
+
+/// 近月、次近月合约的价差
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalendarSpread {
+    pub date: Date,
+    /// 近月合约
+    pub near: Str,
+    /// 次近月合约
+    pub far: Str,
+    pub near_settle: f32,
+    pub far_settle: f32,
+    /// 次近月减近月的结算价
+    pub spread: f32,
+}
+
+/// 同一品种同一交易日的合约，按交割月从近到远排列；跳过结算价为 0 或者无法解析交割月的合约
+fn nearby<'a>(day: impl IntoIterator<Item = &'a Bar>) -> Vec<((i32, u8), &'a Bar)> {
+    let mut v: Vec<_> = day
+        .into_iter()
+        .filter(|b| b.settle != 0.0)
+        .filter_map(|b| Some((util::delivery(&b.code, b.date)?, b)))
+        .collect();
+    v.sort_by_key(|(delivery, _)| *delivery);
+    v
+}
+
+fn is_product(bar: &Bar, product: &str) -> bool {
+    util::product(&bar.code).eq_ignore_ascii_case(product)
+}
+
+/// 品种 `product` 在 `date` 的期限结构；当日没有该品种的数据时返回 None
+pub fn curve(bars: &[Bar], product: &str, date: Date) -> Option<Curve> {
+    let day = nearby(
+        bars.iter()
+            .filter(|b| b.date == date && is_product(b, product)),
+    );
+    let (first, last) = (day.first()?.1, day.last()?.1);
+    let mut prev = first.settle;
+    let points = day
+        .iter()
+        .map(|&(delivery, b)| {
+            let spread = b.settle - prev;
+            prev = b.settle;
+            Point {
+                code: b.code.clone(),
+                delivery,
+                settle: b.settle,
+                close: b.close,
+                position: b.position,
+                spread,
+            }
+        })
+        .collect();
+    Some(Curve {
+        date,
+        product: product.to_uppercase().into(),
+        points,
+        shape: Shape::of(last.settle - first.settle),
+    })
+}
+
+/// 品种 `product` 每个交易日近月、次近月合约的价差，按日期排序；只有一个合约的交易日被跳过
+pub fn calendar_spreads(bars: &[Bar], product: &str) -> Vec<CalendarSpread> {
+    let mut days: BTreeMap<Date, Vec<&Bar>> = BTreeMap::new();
+    for bar in bars.iter().filter(|b| is_product(b, product)) {
+        days.entry(bar.date).or_default().push(bar);
+    }
+    days.into_iter()
+        .filter_map(|(date, day)| match nearby(day)[..] {
+            [(_, near), (_, far), ..] => Some(CalendarSpread {
+                date,
+                near: near.code.clone(),
+                far: far.code.clone(),
+                near_settle: near.settle,
+                far_settle: far.settle,
+                spread: far.settle - near.settle,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 从 `qihuo.ce` 读取品种 `product` 在 `from..=date` 的数据，返回 `date` 的期限结构和这段时间的价差历史
+pub fn run(
+    product: &str,
+    date: Date,
+    from: Option<Date>,
+) -> Result<(Option<Curve>, Vec<CalendarSpread>)> {
+    let selection = ce::Selection {
+        products: vec![product.into()],
+        contracts: Vec::new(),
+        from,
+        to: Some(date),
+    };
+    let bars = ce::load(&selection)?;
+    Ok((
+        curve(&bars, product, date),
+        calendar_spreads(&bars, product),
+    ))
+}
@@ -0,0 +1,196 @@
+//! 品种指数：每个交易日按权重对品种的所有合约加权平均。
+//!
+//! 当日成交量、持仓量作为权重时，收盘后才能确定，因此盘中不可交易；
+//! 前一交易日持仓量（`prev_oi`）不使用当日之后的数据。
+use super::Target;
+use crate::{
+    ce::{self, Bar},
+    util, Exchange, Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use time::Date;
+
+/// 加权方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weight {
+    /// 当日成交量
+    Vol,
+    /// 当日持仓量
+    Oi,
+    /// 前一交易日持仓量：新上市的合约第一天权重为 0
+    #[default]
+    PrevOi,
+}
+
+impl Weight {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Weight::Vol => "vol",
+            Weight::Oi => "oi",
+            Weight::PrevOi => "prev_oi",
+        }
+    }
+}
+
+impl std::str::FromStr for Weight {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "vol" => Weight::Vol,
+            "oi" => Weight::Oi,
+            "prev_oi" | "prev-oi" => Weight::PrevOi,
+            _ => return Err(format!("{s} 不是加权方式，只支持 vol/oi/prev_oi")),
+        })
+    }
+}
+
+/// 品种指数的一天：与 `qihuo.ce_index` 的列一一对应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexBar {
+    pub date: Date,
+    /// 品种代码（大写）
+    pub product: Str,
+    pub open: f32,
+    pub high: f32,
+    pub low: f32,
+    pub close: f32,
+    pub settle: f32,
+    /// 所有合约的成交量之和（单边）
+    pub vol: u64,
+    /// 所有合约的交易额之和（万元）
+    pub amount: f64,
+    /// 所有合约的持仓量之和（单边）
+    pub position: u64,
+    /// 权重不为 0 的合约数量
+    pub contracts: u32,
+    pub weight: Weight,
+    pub ce: Exchange,
+}
+
+/// 根据各合约的日线生成品种指数。`bars` 可以包含多个品种，顺序不限。
+///
+/// 每个价格字段只在该字段不为 0 的合约之间加权（无成交的合约开盘价等为 0）；
+/// 当日所有合约的权重都为 0 时（如 `prev_oi` 下品种的第一个交易日），跳过该日。
+pub fn index(bars: &[Bar], weight: Weight) -> Vec<IndexBar> {
+    let mut groups: BTreeMap<(Exchange, Str), BTreeMap<Date, Vec<&Bar>>> = BTreeMap::new();
+    for bar in bars {
+        let product = Str::from(util::product(&bar.code).to_uppercase());
+        groups
+            .entry((bar.ce, product))
+            .or_default()
+            .entry(bar.date)
+            .or_default()
+            .push(bar);
+    }
+    let mut v = Vec::with_capacity(bars.len() / 8);
+    for ((ce, product), days) in groups {
+        let mut prev_oi: HashMap<&str, u32> = HashMap::new();
+        for (date, day) in days {
+            let weights: Vec<f64> = day
+                .iter()
+                .map(|b| match weight {
+                    Weight::Vol => b.vol as f64,
+                    Weight::Oi => b.position as f64,
+                    Weight::PrevOi => prev_oi.get(b.code.as_str()).copied().unwrap_or(0) as f64,
+                })
+                .collect();
+            prev_oi = day.iter().map(|b| (b.code.as_str(), b.position)).collect();
+            let contracts = weights.iter().filter(|w| **w > 0.0).count() as u32;
+            if contracts == 0 {
+                debug!("{date} {product} 所有合约的权重都为 0，跳过");
+                continue;
+            }
+            let avg = |f: fn(&Bar) -> f32| {
+                let (sum, total) = day
+                    .iter()
+                    .zip(&weights)
+                    .filter(|(b, w)| f(b) != 0.0 && **w > 0.0)
+                    .fold((0.0, 0.0), |(sum, total), (b, w)| {
+                        (sum + f(b) as f64 * w, total + w)
+                    });
+                if total > 0.0 {
+                    (sum / total) as f32
+                } else {
+                    0.0
+                }
+            };
+            v.push(IndexBar {
+                date,
+                product: product.clone(),
+                open: avg(|b| b.open),
+                high: avg(|b| b.high),
+                low: avg(|b| b.low),
+                close: avg(|b| b.close),
+                settle: avg(|b| b.settle),
+                vol: day.iter().map(|b| b.vol as u64).sum(),
+                amount: day.iter().map(|b| b.amount as f64).sum(),
+                position: day.iter().map(|b| b.position as u64).sum(),
+                contracts,
+                weight,
+                ce,
+            });
+        }
+    }
+    v
+}
+
+/// 从 `qihuo.ce` 读取数据，生成品种指数，并保存到 `targets`（表为 `qihuo.ce_index`）
+pub fn run(selection: &ce::Selection, weight: Weight, targets: &[Target]) -> Result<Vec<IndexBar>> {
+    let bars = ce::load(selection)?;
+    let data = index(&bars, weight);
+    let products = super::products(data.iter().map(|b| b.product.as_str()));
+    let condition = format!(
+        " AND weight = '{}'{}",
+        weight.as_str(),
+        super::date_condition(selection)
+    );
+    super::save(&data, &products, "index", &condition, targets, |fname| {
+        save_parquet(&data, fname)
+    })?;
+    Ok(data)
+}
+
+#[cfg(feature = "parquet")]
+fn save_parquet(data: &[IndexBar], fname: &str) -> Result<()> {
+    use crate::util::parquet::{save_parquet, Column};
+    const SCHEMA: &str = "message ce_index {
+        required int32 date (DATE);
+        required binary product (UTF8);
+        required float open;
+        required float high;
+        required float low;
+        required float close;
+        required float settle;
+        required int64 vol;
+        required double amount;
+        required int64 position;
+        required int64 contracts;
+        required binary weight (UTF8);
+        required binary ce (UTF8);
+    }";
+    let float = |f: fn(&IndexBar) -> f32| Column::Float(data.iter().map(f).collect());
+    let ce: Vec<_> = data.iter().map(|b| b.ce.to_string()).collect();
+    let columns = vec![
+        Column::date(data.iter().map(|b| b.date)),
+        Column::str(data.iter().map(|b| b.product.as_str())),
+        float(|b| b.open),
+        float(|b| b.high),
+        float(|b| b.low),
+        float(|b| b.close),
+        float(|b| b.settle),
+        Column::Long(data.iter().map(|b| b.vol as i64).collect()),
+        Column::Double(data.iter().map(|b| b.amount).collect()),
+        Column::Long(data.iter().map(|b| b.position as i64).collect()),
+        Column::Long(data.iter().map(|b| b.contracts as i64).collect()),
+        Column::str(data.iter().map(|b| b.weight.as_str())),
+        Column::str(ce.iter().map(String::as_str)),
+    ];
+    save_parquet(SCHEMA, columns, fname)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+use super::no_parquet as save_parquet;
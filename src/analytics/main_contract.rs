@@ -0,0 +1,295 @@
+//! 主力合约：每个品种每个交易日选出一个合约，拼接成连续的日线序列。
+//!
+//! 每日收盘后按持仓量（或成交量）比较，换月在下一个交易日生效，因此不使用当日之后的数据。
+use super::Target;
+use crate::{
+    ce::{self, Bar},
+    util, Exchange, Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use time::Date;
+
+/// 比较合约的依据
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rank {
+    /// 持仓量
+    #[default]
+    Oi,
+    /// 成交量
+    Vol,
+}
+
+impl Rank {
+    fn of(self, bar: &Bar) -> f64 {
+        match self {
+            Rank::Oi => bar.position as f64,
+            Rank::Vol => bar.vol as f64,
+        }
+    }
+}
+
+impl std::str::FromStr for Rank {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "oi" => Rank::Oi,
+            "vol" => Rank::Vol,
+            _ => return Err(format!("{s} 不是比较依据，只支持 oi/vol")),
+        })
+    }
+}
+
+/// 主力合约的选取规则
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    pub rank: Rank,
+    /// 迟滞：新合约需要超过当前主力合约的比例，如 0.1 表示多出 10%
+    pub threshold: f64,
+    /// 新合约需要连续领先的交易日数（至少为 1）
+    pub confirm_days: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rank: Rank::Oi,
+            threshold: 0.0,
+            confirm_days: 1,
+        }
+    }
+}
+
+/// 主力合约序列中的一天：与 `qihuo.ce_main` 的列一一对应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MainBar {
+    pub date: Date,
+    /// 品种代码（大写）
+    pub product: Str,
+    /// 当日的主力合约
+    pub code: Str,
+    pub open: f32,
+    pub high: f32,
+    pub low: f32,
+    pub close: f32,
+    pub settle: f32,
+    pub vol: u32,
+    pub amount: f32,
+    pub position: u32,
+    pub ce: Exchange,
+    /// 是否为换月后的第一天
+    pub roll: bool,
+}
+
+/// 换月：`date` 当天起主力合约由 `from` 变为 `to`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Roll {
+    pub date: Date,
+    pub product: Str,
+    pub from: Str,
+    pub to: Str,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MainSeries {
+    /// 按品种、日期排序
+    pub bars: Vec<MainBar>,
+    pub rolls: Vec<Roll>,
+}
+
+/// 从各合约的日线中选出每个品种的主力合约序列。`bars` 可以包含多个品种，顺序不限。
+///
+/// 规则：
+/// * 当前主力合约当日没有数据（如已到期）时，立即换成当日排名第一的合约
+/// * 否则当日收盘后，若另一合约领先当前主力合约超过 `threshold`，且连续 `confirm_days` 天，
+///   则下一个交易日换成该合约
+/// * 曾经是主力的合约不会再次成为主力
+pub fn main_contract(bars: &[Bar], opts: &Options) -> MainSeries {
+    let mut groups: BTreeMap<(Exchange, Str), BTreeMap<Date, Vec<&Bar>>> = BTreeMap::new();
+    for bar in bars {
+        let product = Str::from(util::product(&bar.code).to_uppercase());
+        groups
+            .entry((bar.ce, product))
+            .or_default()
+            .entry(bar.date)
+            .or_default()
+            .push(bar);
+    }
+    let mut series = MainSeries::default();
+    for ((_, product), days) in groups {
+        select(&product, days, opts, &mut series);
+    }
+    series
+}
+
+fn select<'a>(
+    product: &Str,
+    days: BTreeMap<Date, Vec<&'a Bar>>,
+    opts: &Options,
+    out: &mut MainSeries,
+) {
+    let rank = |bar: &Bar| opts.rank.of(bar);
+    // 排名第一、且不是当前主力合约和曾经的主力合约
+    let leader =
+        |day: &[&'a Bar], current: &Option<Str>, retired: &HashSet<Str>| -> Option<&'a Bar> {
+            day.iter()
+                .filter(|b| !retired.contains(&b.code) && current.as_ref() != Some(&b.code))
+                .max_by(|a, b| {
+                    rank(a)
+                        .total_cmp(&rank(b))
+                        .then_with(|| b.code.cmp(&a.code))
+                })
+                .copied()
+        };
+    let mut retired = HashSet::new();
+    let mut current: Option<Str> = None;
+    // 收盘后决定、下一个交易日生效的主力合约
+    let mut next: Option<Str> = None;
+    // 待确认的新合约，以及它已经连续领先的天数
+    let mut challenger: Option<(Str, u32)> = None;
+    for (date, day) in days {
+        let mut rolled = false;
+        if let Some(code) = next.take() {
+            rolled |= roll(product, date, &mut current, code, &mut retired, out);
+        }
+        let today = current
+            .as_ref()
+            .and_then(|code| day.iter().find(|b| b.code == *code))
+            .copied();
+        let bar = match today {
+            Some(bar) => bar,
+            // 当前主力合约当日没有数据（如已到期），立即换成当日排名第一的合约
+            None => match leader(&day, &current, &retired) {
+                Some(bar) => {
+                    rolled |= roll(
+                        product,
+                        date,
+                        &mut current,
+                        bar.code.clone(),
+                        &mut retired,
+                        out,
+                    );
+                    challenger = None;
+                    bar
+                }
+                None => continue,
+            },
+        };
+        out.bars.push(MainBar {
+            date,
+            product: product.clone(),
+            code: bar.code.clone(),
+            open: bar.open,
+            high: bar.high,
+            low: bar.low,
+            close: bar.close,
+            settle: bar.settle,
+            vol: bar.vol,
+            amount: bar.amount,
+            position: bar.position,
+            ce: bar.ce,
+            roll: rolled,
+        });
+        challenger = match leader(&day, &current, &retired) {
+            Some(l) if rank(l) > rank(bar) * (1.0 + opts.threshold) => {
+                let count = match challenger {
+                    Some((code, count)) if code == l.code => count + 1,
+                    _ => 1,
+                };
+                if count >= opts.confirm_days.max(1) {
+                    next = Some(l.code.clone());
+                    None
+                } else {
+                    Some((l.code.clone(), count))
+                }
+            }
+            _ => None,
+        };
+    }
+}
+
+/// 换成 `to`，返回是否发生了换月（首次选出主力合约不算换月）
+fn roll(
+    product: &Str,
+    date: Date,
+    current: &mut Option<Str>,
+    to: Str,
+    retired: &mut HashSet<Str>,
+    out: &mut MainSeries,
+) -> bool {
+    match current.replace(to.clone()) {
+        Some(from) => {
+            retired.insert(from.clone());
+            out.rolls.push(Roll {
+                date,
+                product: product.clone(),
+                from,
+                to,
+            });
+            true
+        }
+        None => false,
+    }
+}
+
+/// 从 `qihuo.ce` 读取数据，生成主力合约序列，并保存到 `targets`（表为 `qihuo.ce_main`，覆盖这些品种在选择的日期范围内的旧数据）
+pub fn run(selection: &ce::Selection, opts: &Options, targets: &[Target]) -> Result<MainSeries> {
+    let bars = ce::load(selection)?;
+    let series = main_contract(&bars, opts);
+    let products = super::products(series.bars.iter().map(|b| b.product.as_str()));
+    let condition = super::date_condition(selection);
+    super::save(
+        &series.bars,
+        &products,
+        "main",
+        &condition,
+        targets,
+        |fname| save_parquet(&series.bars, fname),
+    )?;
+    Ok(series)
+}
+
+#[cfg(feature = "parquet")]
+fn save_parquet(data: &[MainBar], fname: &str) -> Result<()> {
+    use crate::util::parquet::{save_parquet, Column};
+    const SCHEMA: &str = "message ce_main {
+        required int32 date (DATE);
+        required binary product (UTF8);
+        required binary code (UTF8);
+        required float open;
+        required float high;
+        required float low;
+        required float close;
+        required float settle;
+        required int64 vol;
+        required float amount;
+        required int64 position;
+        required binary ce (UTF8);
+        required boolean roll;
+    }";
+    let float = |f: fn(&MainBar) -> f32| Column::Float(data.iter().map(f).collect());
+    let long = |f: fn(&MainBar) -> u32| Column::Long(data.iter().map(|b| f(b) as i64).collect());
+    let ce: Vec<_> = data.iter().map(|b| b.ce.to_string()).collect();
+    let columns = vec![
+        Column::date(data.iter().map(|b| b.date)),
+        Column::str(data.iter().map(|b| b.product.as_str())),
+        Column::str(data.iter().map(|b| b.code.as_str())),
+        float(|b| b.open),
+        float(|b| b.high),
+        float(|b| b.low),
+        float(|b| b.close),
+        float(|b| b.settle),
+        long(|b| b.vol),
+        float(|b| b.amount),
+        long(|b| b.position),
+        Column::str(ce.iter().map(String::as_str)),
+        Column::Bool(data.iter().map(|b| b.roll).collect()),
+    ];
+    save_parquet(SCHEMA, columns, fname)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+use super::no_parquet as save_parquet;
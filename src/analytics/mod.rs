@@ -0,0 +1,153 @@
+//! 基于 `qihuo.ce` 统一日线数据的分析：数据通过 [`ce::load`](crate::ce::load) 读取，
+//! 计算在 Rust 中完成，结果可以保存到 csv、parquet 或者 clickhouse 表（见 [`Target`]）。
+use crate::{
+    ce,
+    error::Context,
+    util::{self, clickhouse, Sink},
+    Error, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// 复权连续合约
+pub mod continuous;
+/// 期限结构
+pub mod curve;
+/// 品种指数
+pub mod index;
+/// 主力合约
+pub mod main_contract;
+/// 周线、月线
+pub mod resample;
+
+/// 分析结果的保存方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// 缓存目录下的 csv 文件
+    Csv,
+    /// 缓存目录下的 parquet 文件（需要 `parquet` feature）
+    Parquet,
+    /// clickhouse 表，如 `qihuo.ce_main`
+    Table,
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "csv" => Target::Csv,
+            "parquet" => Target::Parquet,
+            "table" => Target::Table,
+            _ => return Err(format!("{s} 不是保存方式，只支持 csv/parquet/table")),
+        })
+    }
+}
+
+impl Target {
+    /// 配置中的保存方式：csv 对应 csv 文件，clickhouse 对应表
+    pub fn from_config() -> Vec<Target> {
+        let config = util::config();
+        let mut targets = Vec::with_capacity(2);
+        if config.sink_enabled(Sink::Csv) {
+            targets.push(Target::Csv);
+        }
+        if config.sink_enabled(Sink::Clickhouse) {
+            targets.push(Target::Table);
+        }
+        targets
+    }
+}
+
+/// 保存分析结果：
+/// * 文件名为 `ce-{kind}-{product}` 或者 `ce-{kind}`（多个品种时）
+/// * 表名为 `ce_{kind}`：先删除这些品种（以及满足 `condition` 的）旧数据，再录入（见 [`delete_sql`]）
+/// * `parquet` 根据文件名写入 parquet 文件
+fn save<T: Serialize + Debug>(
+    rows: &[T],
+    products: &[&str],
+    kind: &str,
+    condition: &str,
+    targets: &[Target],
+    parquet: impl FnOnce(&str) -> Result<()>,
+) -> Result<()> {
+    let fname = match products {
+        [product] => format!("ce-{kind}-{product}"),
+        _ => format!("ce-{kind}"),
+    };
+    let mut parquet = Some(parquet);
+    for target in targets {
+        match target {
+            Target::Csv => {
+                util::save_csv(&to_csv(rows)?, &fname)?;
+            }
+            Target::Parquet => {
+                if let Some(parquet) = parquet.take() {
+                    parquet(&fname)?;
+                }
+            }
+            Target::Table if !products.is_empty() => {
+                util::migrate::migrate_once()?;
+                let table = clickhouse::table(&format!("ce_{kind}"));
+                clickhouse::execute(&delete_sql(&table, products, condition))?;
+                clickhouse::insert(
+                    &format!("INSERT INTO {table} FORMAT CSV"),
+                    std::io::Cursor::new(to_csv(rows)?),
+                )?;
+                info!("{table} 录入了 {} 条数据", rows.len());
+            }
+            Target::Table => (),
+        }
+    }
+    Ok(())
+}
+
+/// 录入分析结果之前删除旧数据的语句：`condition` 以 ` AND ` 开头，如 [`date_condition`]
+pub fn delete_sql(table: &str, products: &[&str], condition: &str) -> String {
+    let products: Vec<_> = products.iter().map(|p| format!("'{p}'")).collect();
+    format!(
+        "ALTER TABLE {table} DELETE WHERE product IN ({}){condition} SETTINGS mutations_sync = 1",
+        products.join(", "),
+    )
+}
+
+/// 选择的日期范围对应的删除条件：只重新生成部分日期时，不删除范围以外的旧数据
+pub fn date_condition(selection: &ce::Selection) -> String {
+    let mut cond = String::new();
+    if let Some(from) = selection.from {
+        cond.push_str(&format!(" AND date >= '{from}'"));
+    }
+    if let Some(to) = selection.to {
+        cond.push_str(&format!(" AND date <= '{to}'"));
+    }
+    cond
+}
+
+fn to_csv<T: Serialize + Debug>(rows: &[T]) -> Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::with_capacity(rows.len() * 96));
+    for row in rows {
+        writer
+            .serialize(row)
+            .or_err(Error::Row, || format!("{row:?} 无法写入 csv"))?;
+    }
+    writer.flush()?;
+    Ok(writer.into_inner().unwrap_or_default())
+}
+
+/// 按顺序去重后的品种代码
+fn products<'a>(products: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut v: Vec<_> = products.into_iter().collect();
+    v.dedup();
+    v
+}
+
+#[cfg(not(feature = "parquet"))]
+fn no_parquet<T>(_: &[T], fname: &str) -> Result<()> {
+    bail!(
+        Export,
+        "{fname}：未启用 parquet feature，无法写入 parquet 文件"
+    )
+}
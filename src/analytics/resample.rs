@@ -0,0 +1,330 @@
+//! 把日线合成为周线、月线：按实际交易日所在的自然周（ISO 周）或者自然月分组。
+use super::{
+    continuous::{self, ContinuousBar},
+    index::{self, IndexBar},
+    main_contract::{self, MainBar},
+    Target,
+};
+use crate::{
+    ce::{self, Bar},
+    Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::Date;
+
+/// 周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Period {
+    /// 周线
+    W,
+    /// 月线
+    M,
+}
+
+impl std::str::FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "W" | "w" => Period::W,
+            "M" | "m" => Period::M,
+            _ => return Err(format!("{s} 不是周期，只支持 W/M")),
+        })
+    }
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl Period {
+    /// 日期所在的周期：(ISO 年, 周) 或者 (年, 月)
+    fn of(self, date: Date) -> (i32, u8) {
+        match self {
+            Period::W => {
+                let (year, week, _) = date.to_iso_week_date();
+                (year, week)
+            }
+            Period::M => (date.year(), date.month() as u8),
+        }
+    }
+}
+
+/// 合成的对象
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Series {
+    /// 各合约的日线
+    #[default]
+    Contract,
+    /// 主力合约序列（默认参数）
+    Main,
+    /// 复权连续合约（默认参数）
+    Continuous,
+    /// 品种指数（默认权重）
+    Index,
+}
+
+impl Series {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Series::Contract => "contract",
+            Series::Main => "main",
+            Series::Continuous => "continuous",
+            Series::Index => "index",
+        }
+    }
+}
+
+impl std::str::FromStr for Series {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "contract" => Series::Contract,
+            "main" => Series::Main,
+            "continuous" => Series::Continuous,
+            "index" => Series::Index,
+            _ => {
+                return Err(format!(
+                    "{s} 不是序列，只支持 contract/main/continuous/index"
+                ))
+            }
+        })
+    }
+}
+
+/// 可以合成的日线
+pub trait Daily {
+    /// 价格为 0 是否表示无成交：只适用于原始合约的价格；复权后的价格可以为 0 或者负数
+    const ZERO_IS_NO_TRADE: bool = false;
+    fn date(&self) -> Date;
+    /// 分组的依据：合约代码或者品种代码
+    fn key(&self) -> &str;
+    /// 开盘价、最高价、最低价、收盘价、结算价
+    fn prices(&self) -> [f32; 5];
+    /// 成交量、交易额、持仓量；没有这些数据时为 0
+    fn volume(&self) -> (u64, f64, u64) {
+        (0, 0.0, 0)
+    }
+}
+
+impl Daily for Bar {
+    const ZERO_IS_NO_TRADE: bool = true;
+    fn date(&self) -> Date {
+        self.date
+    }
+    fn key(&self) -> &str {
+        &self.code
+    }
+    fn prices(&self) -> [f32; 5] {
+        [self.open, self.high, self.low, self.close, self.settle]
+    }
+    fn volume(&self) -> (u64, f64, u64) {
+        (self.vol as u64, self.amount as f64, self.position as u64)
+    }
+}
+
+impl Daily for MainBar {
+    const ZERO_IS_NO_TRADE: bool = true;
+    fn date(&self) -> Date {
+        self.date
+    }
+    fn key(&self) -> &str {
+        &self.product
+    }
+    fn prices(&self) -> [f32; 5] {
+        [self.open, self.high, self.low, self.close, self.settle]
+    }
+    fn volume(&self) -> (u64, f64, u64) {
+        (self.vol as u64, self.amount as f64, self.position as u64)
+    }
+}
+
+impl Daily for ContinuousBar {
+    fn date(&self) -> Date {
+        self.date
+    }
+    fn key(&self) -> &str {
+        &self.product
+    }
+    fn prices(&self) -> [f32; 5] {
+        [self.open, self.high, self.low, self.close, self.settle]
+    }
+    fn volume(&self) -> (u64, f64, u64) {
+        (self.vol as u64, self.amount as f64, self.position as u64)
+    }
+}
+
+impl Daily for IndexBar {
+    fn date(&self) -> Date {
+        self.date
+    }
+    fn key(&self) -> &str {
+        &self.product
+    }
+    fn prices(&self) -> [f32; 5] {
+        [self.open, self.high, self.low, self.close, self.settle]
+    }
+    fn volume(&self) -> (u64, f64, u64) {
+        (self.vol, self.amount, self.position)
+    }
+}
+
+/// 合成后的一根 K 线
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Resampled {
+    /// 合约代码或者品种代码
+    pub key: Str,
+    pub period: Period,
+    /// 周期内第一个交易日
+    pub start: Date,
+    /// 周期内最后一个交易日
+    pub end: Date,
+    /// 第一个有成交的交易日的开盘价
+    pub open: f32,
+    pub high: f32,
+    pub low: f32,
+    /// 最后一个交易日的收盘价
+    pub close: f32,
+    /// 最后一个交易日的结算价
+    pub settle: f32,
+    /// 成交量之和
+    pub vol: u64,
+    /// 交易额之和
+    pub amount: f64,
+    /// 最后一个交易日的持仓量
+    pub position: u64,
+    /// 交易日数量
+    pub days: u32,
+}
+
+/// 按 `key`、周期合成，结果按 `key`、日期排序。
+/// 原始合约价格为 0（无成交，见 [`Daily::ZERO_IS_NO_TRADE`]）的开盘价、最高价、最低价不参与合成。
+pub fn resample<T: Daily>(rows: &[T], period: Period) -> Vec<Resampled> {
+    let mut groups: BTreeMap<(&str, (i32, u8)), Vec<&T>> = BTreeMap::new();
+    for row in rows {
+        groups
+            .entry((row.key(), period.of(row.date())))
+            .or_default()
+            .push(row);
+    }
+    groups
+        .into_iter()
+        .map(|((key, _), mut days)| {
+            days.sort_by_key(|d| d.date());
+            let (first, last) = (days[0], days[days.len() - 1]);
+            let prices = |i: usize| {
+                days.iter()
+                    .map(move |d| d.prices()[i])
+                    .filter(|p| !T::ZERO_IS_NO_TRADE || *p != 0.0)
+            };
+            let [_, _, _, close, settle] = last.prices();
+            let (vol, amount) = days.iter().fold((0, 0.0), |(vol, amount), d| {
+                let (v, a, _) = d.volume();
+                (vol + v, amount + a)
+            });
+            Resampled {
+                key: key.into(),
+                period,
+                start: first.date(),
+                end: last.date(),
+                open: prices(0).next().unwrap_or(0.0),
+                high: prices(1).reduce(f32::max).unwrap_or(0.0),
+                low: prices(2).reduce(f32::min).unwrap_or(0.0),
+                close,
+                settle,
+                vol,
+                amount,
+                position: last.volume().2,
+                days: days.len() as u32,
+            }
+        })
+        .collect()
+}
+
+/// 从 `qihuo.ce` 读取数据，生成 `series` 并合成，保存到 `targets`：
+/// 文件名为 `ce-{series}-{w|m}-{product}` 或者 `ce-{series}-{w|m}`；不支持保存到表。
+pub fn run(
+    selection: &ce::Selection,
+    series: Series,
+    period: Period,
+    targets: &[Target],
+) -> Result<Vec<Resampled>> {
+    ensure!(
+        !targets.contains(&Target::Table),
+        Export,
+        "周线、月线只能保存到 csv 或者 parquet 文件"
+    );
+    let bars = ce::load(selection)?;
+    let data = match series {
+        Series::Contract => resample(&bars, period),
+        Series::Main => {
+            let main = main_contract::main_contract(&bars, &Default::default());
+            resample(&main.bars, period)
+        }
+        Series::Continuous => {
+            let main = main_contract::main_contract(&bars, &Default::default());
+            resample(
+                &continuous::continuous(&bars, &main, Default::default()),
+                period,
+            )
+        }
+        Series::Index => resample(&index::index(&bars, Default::default()), period),
+    };
+    let mut products: Vec<_> = data
+        .iter()
+        .map(|r| crate::util::product(&r.key).to_uppercase())
+        .collect();
+    products.dedup();
+    let products: Vec<_> = products.iter().map(String::as_str).collect();
+    let kind = format!("{}-{}", series.as_str(), period.to_string().to_lowercase());
+    super::save(&data, &products, &kind, "", targets, |fname| {
+        save_parquet(&data, fname)
+    })?;
+    Ok(data)
+}
+
+#[cfg(feature = "parquet")]
+fn save_parquet(data: &[Resampled], fname: &str) -> Result<()> {
+    use crate::util::parquet::{save_parquet, Column};
+    const SCHEMA: &str = "message ce_resampled {
+        required binary key (UTF8);
+        required binary period (UTF8);
+        required int32 start (DATE);
+        required int32 end (DATE);
+        required float open;
+        required float high;
+        required float low;
+        required float close;
+        required float settle;
+        required int64 vol;
+        required double amount;
+        required int64 position;
+        required int64 days;
+    }";
+    let float = |f: fn(&Resampled) -> f32| Column::Float(data.iter().map(f).collect());
+    let period: Vec<_> = data.iter().map(|r| r.period.to_string()).collect();
+    let columns = vec![
+        Column::str(data.iter().map(|r| r.key.as_str())),
+        Column::str(period.iter().map(String::as_str)),
+        Column::date(data.iter().map(|r| r.start)),
+        Column::date(data.iter().map(|r| r.end)),
+        float(|r| r.open),
+        float(|r| r.high),
+        float(|r| r.low),
+        float(|r| r.close),
+        float(|r| r.settle),
+        Column::Long(data.iter().map(|r| r.vol as i64).collect()),
+        Column::Double(data.iter().map(|r| r.amount).collect()),
+        Column::Long(data.iter().map(|r| r.position as i64).collect()),
+        Column::Long(data.iter().map(|r| r.days as i64).collect()),
+    ];
+    save_parquet(SCHEMA, columns, fname)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+use super::no_parquet as save_parquet;
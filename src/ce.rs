@@ -1,12 +1,283 @@
-use crate::{util::clickhouse, Result};
-use color_eyre::eyre::Context;
+use crate::{
+    czce, dce,
+    error::Context,
+    util::{self, clickhouse, Sink},
+    Error, Exchange, Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+/// 根据 czce、dce、dce_dsp 表重新生成 `qihuo.ce` 的 SQL 模板，见 [`clickhouse::render`]
+pub const SQL: &str = include_str!("./sql/ce.sql");
 
 pub fn run() -> Result<()> {
-    let count = clickhouse::execute(include_str!("./sql/ce.sql"))?;
+    if !util::config().sink_enabled(Sink::Clickhouse) {
+        debug!("未启用 clickhouse，不重新录入 qihuo.ce");
+        return Ok(());
+    }
+    // ce.sql 依赖 czce、dce、dce_dsp 表
+    util::migrate::migrate_once()?;
+    let count = clickhouse::execute(&clickhouse::render(SQL))?;
     let count = count
         .trim()
         .parse::<u32>()
-        .with_context(|| format!("{count} 无法解析为 u32"))?;
-    info!("qihuo.ce: 重新录入 {count} 条数据",);
+        .or_err(Error::Database, || format!("{count} 无法解析为 u32"))?;
+    info!("{}: 重新录入 {count} 条数据", clickhouse::table("ce"));
     Ok(())
 }
+
+/// 从 `qihuo.ce` 读取数据的条件：各项为空表示不限制
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Selection {
+    /// 品种代码，如 `MA`、`V`（不区分大小写）
+    pub products: Vec<Str>,
+    /// 合约代码，如 `MA401`、`V2401`（不区分大小写）
+    pub contracts: Vec<Str>,
+    /// 起始日期（包含）
+    pub from: Option<Date>,
+    /// 结束日期（包含）
+    pub to: Option<Date>,
+}
+
+impl Selection {
+    /// 生成 SQL 的 WHERE 条件；品种代码只能由字母组成
+    pub fn where_clause(&self) -> Result<String> {
+        let mut cond = vec!["1".to_owned()];
+        if !self.products.is_empty() {
+            for p in &self.products {
+                ensure!(
+                    !p.is_empty() && p.chars().all(|c| c.is_ascii_alphabetic()),
+                    Row,
+                    "{p} 不是品种代码"
+                );
+            }
+            let products: Vec<_> = self
+                .products
+                .iter()
+                .map(|p| format!("'{}'", p.to_uppercase()))
+                .collect();
+            cond.push(format!(
+                "extract(code, '^[A-Z]+') IN ({})",
+                products.join(", ")
+            ));
+        }
+        if !self.contracts.is_empty() {
+            for c in &self.contracts {
+                ensure!(
+                    !c.is_empty() && c.chars().all(|c| c.is_ascii_alphanumeric()),
+                    Row,
+                    "{c} 不是合约代码"
+                );
+            }
+            let contracts: Vec<_> = self
+                .contracts
+                .iter()
+                .map(|c| format!("'{}'", c.to_uppercase()))
+                .collect();
+            cond.push(format!("code IN ({})", contracts.join(", ")));
+        }
+        if let Some(from) = self.from {
+            cond.push(format!("date >= '{from}'"));
+        }
+        if let Some(to) = self.to {
+            cond.push(format!("date <= '{to}'"));
+        }
+        Ok(cond.join(" AND "))
+    }
+
+    /// 与 [`Selection::where_clause`] 相同的条件，用于筛选已读取的数据
+    pub fn matches(&self, bar: &Bar) -> bool {
+        let product = util::product(&bar.code);
+        (self.products.is_empty()
+            || self
+                .products
+                .iter()
+                .any(|p| p.eq_ignore_ascii_case(product)))
+            && (self.contracts.is_empty()
+                || self
+                    .contracts
+                    .iter()
+                    .any(|c| c.eq_ignore_ascii_case(&bar.code)))
+            && self.from.is_none_or(|from| from <= bar.date)
+            && self.to.is_none_or(|to| bar.date <= to)
+    }
+}
+
+/// 按条件读取 `qihuo.ce` 的数据，按日期和合约代码排序
+pub fn load(selection: &Selection) -> Result<Vec<Bar>> {
+    let sql = format!(
+        "SELECT * FROM {} WHERE {} ORDER BY date, code \
+         SETTINGS format_csv_null_representation = '' FORMAT CSV",
+        clickhouse::table("ce"),
+        selection.where_clause()?
+    );
+    let csv = clickhouse::query(&sql)?;
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv.as_bytes())
+        .deserialize()
+        .map(|row| row.or_err(Error::Row, || "qihuo.ce 的数据无法解析".into()))
+        .collect()
+}
+
+/// 从缓存目录下的 csv 文件读取数据，按照与 `ce.sql` 相同的规则统一，按日期和合约代码排序：
+/// * `czce-*.csv`：郑州交易所的年数据和日行情
+/// * `dce-*.csv`（除 `dce-dsp-*.csv`）：大连交易所的年数据和日行情；交割结算价不会被补充
+///
+/// 同一合约同一交易日出现在多个文件中时，按文件名排序后的最后一个为准（日行情优先于年数据）。
+pub fn load_cache(selection: &Selection) -> Result<Vec<Bar>> {
+    load_cache_dir(&util::init_data().cache_dir, selection)
+}
+
+/// 与 [`load_cache`] 相同，但读取 `dir` 下的 csv 文件。
+/// 无法读取或者解析的文件（如早期版本保存的格式）被跳过并记录警告，不影响其他文件。
+pub fn load_cache_dir(dir: &std::path::Path, selection: &Selection) -> Result<Vec<Bar>> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".csv"))
+        .collect();
+    files.sort();
+    let mut bars = std::collections::BTreeMap::new();
+    for name in files {
+        let exchange = if name.starts_with("czce-") {
+            Exchange::czce
+        } else if name.starts_with("dce-") && !name.starts_with("dce-dsp-") {
+            Exchange::dce
+        } else {
+            continue;
+        };
+        let path = dir.join(&name);
+        let rows = match read_cache_file(&path, exchange) {
+            Ok(rows) => rows,
+            Err(err) => {
+                warn!("跳过 {name}：{err}");
+                continue;
+            }
+        };
+        debug!("{name} 读取了 {} 条数据", rows.len());
+        for bar in rows.into_iter().filter(|b| selection.matches(b)) {
+            bars.insert((bar.date, bar.code.clone()), bar);
+        }
+    }
+    Ok(bars.into_values().collect())
+}
+
+fn read_cache_file(path: &std::path::Path, exchange: Exchange) -> Result<Vec<Bar>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .or_err(Error::Row, || format!("{} 无法读取", path.display()))?;
+    let err = || format!("{} 的数据无法解析", path.display());
+    match exchange {
+        Exchange::czce => reader
+            .deserialize::<czce::Data>()
+            .map(|row| row.map(Bar::from).or_err(Error::Row, err))
+            .collect(),
+        Exchange::dce => reader
+            .deserialize::<dce::Data>()
+            .map(|row| row.map(Bar::from).or_err(Error::Row, err))
+            .collect(),
+    }
+}
+
+/// 按照配置读取数据：启用 clickhouse 时读取 `qihuo.ce`（见 [`load`]），否则读取缓存的 csv 文件（见 [`load_cache`]）
+pub fn query(selection: &Selection) -> Result<Vec<Bar>> {
+    if util::config().sink_enabled(Sink::Clickhouse) {
+        load(selection)
+    } else {
+        load_cache(selection)
+    }
+}
+
+/// 各交易所统一后的日线数据，与 `qihuo.ce` 的列一一对应：
+/// * 合约代码统一为大写
+/// * 成交量、持仓量、成交额统一为单边
+/// * 成交额统一为万元
+///
+/// 转换规则与 `ce.sql` 一致。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "tabled", derive(tabled::Tabled))]
+pub struct Bar {
+    /// 交易日期
+    pub date: Date,
+    /// 合约代码
+    pub code: Str,
+    /// 开盘价
+    pub open: f32,
+    /// 最高价
+    pub high: f32,
+    /// 最低价
+    pub low: f32,
+    /// 收盘价
+    pub close: f32,
+    /// 结算价
+    pub settle: f32,
+    /// 成交量（单边）
+    pub vol: u32,
+    /// 交易额（万元）
+    pub amount: f32,
+    /// 持仓量（单边）
+    pub position: u32,
+    /// 交易所
+    pub ce: Exchange,
+    /// 前收盘价：只有 dce 提供（且并非每年都有）
+    #[cfg_attr(
+        feature = "tabled",
+        tabled(display_with = "crate::util::display_option")
+    )]
+    pub prev_close: Option<f32>,
+    /// 交割结算价：czce 来自日行情，dce 来自单独发布的交割结算价（见 [`dce::fetch_dsp`]）
+    #[cfg_attr(
+        feature = "tabled",
+        tabled(display_with = "crate::util::display_option")
+    )]
+    pub dsp: Option<f32>,
+}
+
+impl From<czce::Data> for Bar {
+    /// 2020-01-01 之前为双边数据，需要减半；成交额单位已经是万元。
+    fn from(d: czce::Data) -> Self {
+        let (vol, amount, position) = if d.date < czce::SINGLE_SIDED_SINCE {
+            (d.vol / 2, d.amount / 2.0, d.position / 2)
+        } else {
+            (d.vol, d.amount, d.position)
+        };
+        Bar {
+            date: d.date,
+            code: d.code.to_uppercase().into(),
+            open: d.open,
+            high: d.high,
+            low: d.low,
+            close: d.close,
+            settle: d.settle,
+            vol,
+            amount,
+            position,
+            ce: Exchange::czce,
+            prev_close: None,
+            dsp: d.dsp,
+        }
+    }
+}
+
+impl From<dce::Data> for Bar {
+    /// 成交量、持仓量为双边，需要减半；成交额单位为元，需要转换成万元。
+    fn from(d: dce::Data) -> Self {
+        Bar {
+            date: d.date,
+            code: d.code.to_uppercase().into(),
+            open: d.open,
+            high: d.high,
+            low: d.low,
+            close: d.close,
+            settle: d.settle,
+            vol: d.vol / 2,
+            amount: (d.amount as f64 / 10000.0) as f32,
+            position: d.position / 2,
+            ce: Exchange::dce,
+            prev_close: d.prev_close,
+            // 由 dce_dsp 表补充
+            dsp: None,
+        }
+    }
+}
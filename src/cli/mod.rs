@@ -1,26 +1,656 @@
 use crate::{Result, Str};
 use argh::FromArgs;
-use commodity_exchange_zh::{ce, czce, dce};
-use regex::Regex;
+use color_eyre::eyre::{bail, ensure};
+use commodity_exchange_zh::{
+    analytics::{continuous, curve, index, main_contract, resample, Target},
+    ce, czce, dce,
+    plan::{self, Plan, Planner},
+    status, util,
+    util::{
+        event::{self, ErrorInfo, Event, Output},
+        migrate, progress,
+        year::{Year, YearSpan},
+    },
+    Error, Exchange,
+};
+use std::path::PathBuf;
+use time::{format_description::FormatItem, macros::format_description, Date, Weekday};
 
 #[doc = "\
 下载、解析和保存商品期货交易所数据。子命令示例：
 
 * `czce -y 2010..2023`：下载郑州交易所 2010 至 2022 年所有合约数据
-* `dce -y 2020..=2023 C M`：下载大连交易所 2019 至 2022 年玉米和豆粕两个品种的数据
+* `czce -y 2015,2018..`：下载郑州交易所 2015 年以及 2018 年至今的所有合约数据
+* `czce --from 2023-03-01 --to 2023-06-30 MA TA`：只录入郑州交易所该时段内甲醇和 PTA 的数据
+* `czce --daily 2023-10-19`：只下载和录入郑州交易所该交易日的数据
+* `dce -y 2020..=2022 玉米 豆粕`：下载大连交易所 2020 至 2022 年玉米和豆粕两个品种的数据
 * `dce`：交互式选择大连交易所年份和品种
+* `dce links refresh`：从大连交易所网页刷新下载链接（新的年份和品种）
+* `dce --daily 2024-05-06..=2024-05-10`：获取大连交易所这些交易日的所有合约日行情
+* `dce dsp -y 2023`：获取大连交易所 2023 年的交割结算价
+* `db migrate`：升级 clickhouse 中已有的表结构；`db status` 查看迁移状态
+* `build main --product MA`：根据 qihuo.ce 生成甲醇的主力合约序列，并显示换月日期
+* `build continuous --product MA --method diff --sink csv`：生成甲醇价差前复权的连续合约
+* `build index --product MA --weight prev_oi`：生成按前一交易日持仓量加权的甲醇指数
+* `query --product MA --from 2023-01-01 --contract MA401`：以表格显示已保存的甲醇 MA401 合约数据；`--format csv|json` 便于管道处理
+* `status`：显示各交易所每年每个品种的数据条数、起止日期和下载时间，并标出大连交易所从未录入的品种
+* `curve MA --date 2023-10-19`：显示甲醇该交易日的期限结构，以及近一年近月、次近月合约的价差
+* `resample --period W --product MA`：把甲醇各合约的日线合成为周线；`--series continuous` 则合成连续合约
+* `--dry-run dce -y 2006.. 玉米`：不联网、不访问数据库，只显示将要下载的链接、录入的表和执行的 SQL
 "]
 #[derive(FromArgs, Debug)]
 pub struct Args {
+    /// 配置文件路径，默认为 `~/.config/ce/config.toml`；也可通过环境变量 `CE_CONFIG` 指定。
+    #[argh(option)]
+    config: Option<PathBuf>,
+
+    /// 同时进行的下载数，如 `--jobs 4`；覆盖配置文件中的 `network.jobs`（默认为 1，即逐个下载）。
+    /// 解析和录入仍按顺序依次进行。
+    #[argh(option)]
+    jobs: Option<usize>,
+
+    /// 输出方式：text（默认，终端日志）或者 json（每行一个 JSON 事件，最后一行为结果，终端日志只写到标准错误）。
+    #[argh(option, default = "Output::Text")]
+    output: Output,
+
+    /// 试运行：不联网、不访问数据库，只显示将要下载的链接、保存的文件、录入的表和执行的 SQL。
+    /// 适用于 czce、dce（交互式选择除外）和 db migrate。
+    #[argh(switch)]
+    dry_run: bool,
+
     #[argh(subcommand)]
-    exchange: Exchange,
+    command: Command,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand)]
-enum Exchange {
+enum Command {
     Czce(Czce),
     Dce(Dce),
+    Db(Db),
+    Build(Build),
+    Resample(Resample),
+    Curve(Curve),
+    Query(Query),
+    Status(CoverageStatus),
+}
+
+impl Command {
+    /// 子命令名，用于结构化输出的结果
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Czce(_) => "czce",
+            Command::Dce(_) => "dce",
+            Command::Db(_) => "db",
+            Command::Build(_) => "build",
+            Command::Resample(_) => "resample",
+            Command::Curve(_) => "curve",
+            Command::Query(_) => "query",
+            Command::Status(_) => "status",
+        }
+    }
+}
+
+/// 显示数据覆盖情况：每个 (交易所, 年份, 品种) 的条数、起止日期和缓存文件的下载时间，
+/// 并标出大连交易所下载链接中从未录入的 (年份, 品种)。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "status")]
+struct CoverageStatus {
+    /// 只显示从未录入的 (年份, 品种)。
+    #[argh(switch)]
+    missing: bool,
+
+    /// 显示下载记录（缓存目录下的 manifest.jsonl）：链接、时间、字节数、SHA-256、编码、解析条数和录入的表。
+    #[argh(switch)]
+    history: bool,
+}
+
+impl CoverageStatus {
+    fn run(self) -> Result<()> {
+        const FMT: &[FormatItem<'static>] =
+            format_description!("[year]-[month]-[day] [hour]:[minute]");
+        if self.history {
+            ensure!(!self.missing, "--history 与 --missing 不能同时使用");
+            let history = util::manifest::history()?;
+            if event::json() {
+                event::set_data(&history);
+                return Ok(());
+            }
+            println!("下载时间\t链接\t字节数\tSHA-256\t编码\t文件\t解析\t跳过\t表\t录入\t去重");
+            for e in history {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    e.fetched_at.format(FMT)?,
+                    e.url,
+                    e.bytes,
+                    &e.sha256[..e.sha256.len().min(12)],
+                    e.encoding
+                        .map_or_else(|| "-".to_owned(), |e| format!("{e:?}")),
+                    if e.files.is_empty() {
+                        "-".to_owned()
+                    } else {
+                        e.files.join(",")
+                    },
+                    e.parsed,
+                    e.rejected,
+                    table_or_error(&e),
+                    e.inserted.map_or_else(|| "-".to_owned(), |n| n.to_string()),
+                    e.deduplicated
+                        .map_or_else(|| "-".to_owned(), |n| n.to_string()),
+                );
+            }
+            return Ok(());
+        }
+        let coverage = status::run()?;
+        if event::json() {
+            let rows: Vec<_> = coverage
+                .iter()
+                .filter(|c| !self.missing || c.missing())
+                .collect();
+            event::set_data(&rows);
+            return Ok(());
+        }
+        let opt = |d: Option<Date>| d.map_or_else(|| "-".to_owned(), |d| d.to_string());
+        println!("交易所\t年份\t品种\t名称\t条数\t起始\t结束\t下载时间");
+        for c in coverage.iter().filter(|c| !self.missing || c.missing()) {
+            let fetched = c.fetched.and_then(|t| t.format(FMT).ok());
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}{}",
+                c.ce,
+                c.year,
+                if c.product.is_empty() {
+                    "-"
+                } else {
+                    &c.product
+                },
+                c.name.as_deref().unwrap_or("-"),
+                c.rows,
+                opt(c.first),
+                opt(c.last),
+                fetched.as_deref().unwrap_or("-"),
+                if c.missing() { "\t未录入" } else { "" },
+            );
+        }
+        let missing = coverage.iter().filter(|c| c.missing()).count();
+        if missing != 0 {
+            warn!("大连交易所下载链接中有 {missing} 个 (年份, 品种) 从未录入，可使用 `ce status --missing` 查看");
+        }
+        Ok(())
+    }
+}
+
+/// 读取已保存的统一日线数据：启用 clickhouse 时读取 qihuo.ce，否则读取缓存目录下的 csv 文件。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "query")]
+struct Query {
+    /// 品种代码，可多次指定，如 `--product MA --product V`；不指定表示所有品种。
+    #[argh(option)]
+    product: Vec<Str>,
+
+    /// 合约代码，可多次指定，如 `--contract MA401`；不指定表示所有合约。
+    #[argh(option)]
+    contract: Vec<Str>,
+
+    /// 起始日期，如 `--from 2023-01-01`。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+
+    /// 结束日期，如 `--to 2023-12-31`。
+    #[argh(option, from_str_fn(parse_date))]
+    to: Option<Date>,
+
+    /// 输出格式：table（需要 tabled feature，启用时为默认）、csv（带表头）或者 json。
+    #[argh(option, default = "Default::default()")]
+    format: Format,
+}
+
+/// `query` 的输出格式
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        if cfg!(feature = "tabled") {
+            Format::Table
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "table" => Format::Table,
+            "csv" => Format::Csv,
+            "json" => Format::Json,
+            _ => return Err(format!("{s} 不是输出格式，只支持 table/csv/json")),
+        })
+    }
+}
+
+impl Query {
+    fn run(self) -> Result<()> {
+        let selection = ce::Selection {
+            products: self.product,
+            contracts: self.contract,
+            from: self.from,
+            to: self.to,
+        };
+        let bars = ce::query(&selection)?;
+        info!("读取了 {} 条数据", bars.len());
+        if event::json() {
+            event::set_data(&bars);
+            return Ok(());
+        }
+        let stdout = std::io::stdout().lock();
+        match self.format {
+            #[cfg(feature = "tabled")]
+            Format::Table => {
+                use std::io::Write;
+                let mut stdout = stdout;
+                writeln!(stdout, "{}", tabled::Table::new(&bars))?;
+            }
+            #[cfg(not(feature = "tabled"))]
+            Format::Table => {
+                bail!(
+                    "未启用 tabled feature，无法以表格显示；请使用 --format csv 或者 --format json"
+                )
+            }
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(stdout);
+                for bar in &bars {
+                    writer.serialize(bar)?;
+                }
+                writer.flush()?;
+            }
+            Format::Json => {
+                use std::io::Write;
+                let mut stdout = stdout;
+                serde_json::to_writer(&mut stdout, &bars)?;
+                writeln!(stdout)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 显示品种某个交易日的期限结构（各合约按交割月排列的结算价），以及近月、次近月合约的价差历史。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "curve")]
+struct Curve {
+    /// 品种代码，如 `MA`。
+    #[argh(positional)]
+    product: Str,
+
+    /// 交易日，如 `--date 2023-10-19`。
+    #[argh(option, from_str_fn(parse_date))]
+    date: Date,
+
+    /// 价差历史的起始日期，默认为 `--date` 之前 365 天。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+}
+
+impl Curve {
+    fn run(self) -> Result<()> {
+        let from = self.from.unwrap_or(self.date - time::Duration::days(365));
+        let (curve, spreads) = curve::run(&self.product, self.date, Some(from))?;
+        let Some(curve) = curve else {
+            bail!("{} 在 {} 没有数据", self.product, self.date);
+        };
+        if event::json() {
+            event::set_data(&serde_json::json!({ "curve": curve, "spreads": spreads }));
+            return Ok(());
+        }
+        println!(
+            "{} {} 期限结构：{:?}",
+            curve.date, curve.product, curve.shape
+        );
+        println!("合约\t交割月\t结算价\t收盘价\t持仓量\t价差");
+        for p in &curve.points {
+            let (year, month) = p.delivery;
+            println!(
+                "{}\t{year}-{month:02}\t{}\t{}\t{}\t{:+}",
+                p.code, p.settle, p.close, p.position, p.spread
+            );
+        }
+        println!("\n近月、次近月价差（次近月 - 近月）：");
+        for s in &spreads {
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{:+}",
+                s.date, s.near, s.near_settle, s.far, s.far_settle, s.spread
+            );
+        }
+        Ok(())
+    }
+}
+
+/// 把 qihuo.ce 的日线（或者衍生序列）合成为周线、月线，保存到 csv 或者 parquet 文件。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "resample")]
+struct Resample {
+    /// 周期：W（按自然周）或者 M（按自然月）。
+    #[argh(option)]
+    period: resample::Period,
+
+    /// 合成的对象：contract（各合约，默认）、main（主力合约）、continuous（复权连续合约）或者 index（品种指数）；
+    /// 后三者使用 `build` 子命令的默认参数。
+    #[argh(option, default = "Default::default()")]
+    series: resample::Series,
+
+    /// 品种代码，可多次指定，如 `--product MA --product V`；不指定表示所有品种。
+    #[argh(option)]
+    product: Vec<Str>,
+
+    /// 保存方式：csv（默认）或者 parquet（需要 parquet feature），可多次指定。
+    #[argh(option)]
+    sink: Vec<Target>,
+
+    /// 起始日期，如 `--from 2023-01-01`。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+
+    /// 结束日期，如 `--to 2023-12-31`。
+    #[argh(option, from_str_fn(parse_date))]
+    to: Option<Date>,
+}
+
+impl Resample {
+    fn run(self) -> Result<()> {
+        let selection = ce::Selection {
+            products: self.product,
+            contracts: Vec::new(),
+            from: self.from,
+            to: self.to,
+        };
+        let sink = if self.sink.is_empty() {
+            vec![Target::Csv]
+        } else {
+            self.sink
+        };
+        let data = resample::run(&selection, self.series, self.period, &sink)?;
+        info!("合成了 {} 条 {} 线数据", data.len(), self.period);
+        event::set_data(&serde_json::json!({ "rows": data.len() }));
+        Ok(())
+    }
+}
+
+/// 根据 qihuo.ce 的数据生成衍生序列
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "build")]
+struct Build {
+    #[argh(subcommand)]
+    command: BuildCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum BuildCommand {
+    Main(BuildMain),
+    Continuous(BuildContinuous),
+    Index(BuildIndex),
+}
+
+/// 生成品种指数（所有合约加权平均），保存到 csv、parquet 或者 qihuo.ce_index。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "index")]
+struct BuildIndex {
+    /// 品种代码，可多次指定，如 `--product MA --product V`；不指定表示所有品种。
+    #[argh(option)]
+    product: Vec<Str>,
+
+    /// 加权方式：prev_oi（前一交易日持仓量，默认）、oi（当日持仓量）或者 vol（当日成交量）。
+    #[argh(option, default = "Default::default()")]
+    weight: index::Weight,
+
+    /// 保存方式：csv、parquet（需要 parquet feature）或者 table，可多次指定；默认按照配置中的 sinks。
+    #[argh(option)]
+    sink: Vec<Target>,
+
+    /// 起始日期，如 `--from 2023-01-01`。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+
+    /// 结束日期，如 `--to 2023-12-31`。
+    #[argh(option, from_str_fn(parse_date))]
+    to: Option<Date>,
+}
+
+/// 生成复权连续合约（基于主力合约序列），保存到 csv、parquet 或者 qihuo.ce_continuous。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "continuous")]
+struct BuildContinuous {
+    /// 品种代码，可多次指定，如 `--product MA --product V`；不指定表示所有品种。
+    #[argh(option)]
+    product: Vec<Str>,
+
+    /// 复权方式：ratio（比例，默认）或者 diff（价差）。
+    #[argh(option, default = "Default::default()")]
+    method: continuous::Method,
+
+    /// 复权方向：backward（前复权，默认，最新价格不变）或者 forward（后复权，最早价格不变）。
+    #[argh(option, default = "Default::default()")]
+    direction: continuous::Direction,
+
+    /// 保存方式：csv、parquet（需要 parquet feature）或者 table，可多次指定；默认按照配置中的 sinks。
+    #[argh(option)]
+    sink: Vec<Target>,
+
+    /// 主力合约的比较依据：oi（持仓量，默认）或者 vol（成交量）。
+    #[argh(option, default = "Default::default()")]
+    by: main_contract::Rank,
+
+    /// 新合约需要超过当前主力合约的比例，默认为 0。
+    #[argh(option, default = "0.0")]
+    threshold: f64,
+
+    /// 新合约需要连续领先的交易日数，默认为 1。
+    #[argh(option, default = "1")]
+    confirm: u32,
+
+    /// 起始日期，如 `--from 2023-01-01`。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+
+    /// 结束日期，如 `--to 2023-12-31`。
+    #[argh(option, from_str_fn(parse_date))]
+    to: Option<Date>,
+}
+
+/// 生成主力合约序列，保存到 csv、parquet 或者 qihuo.ce_main，并显示换月日期。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "main")]
+struct BuildMain {
+    /// 品种代码，可多次指定，如 `--product MA --product V`；不指定表示所有品种。
+    #[argh(option)]
+    product: Vec<Str>,
+
+    /// 比较依据：oi（持仓量，默认）或者 vol（成交量）。
+    #[argh(option, default = "Default::default()")]
+    by: main_contract::Rank,
+
+    /// 保存方式：csv、parquet（需要 parquet feature）或者 table，可多次指定；默认按照配置中的 sinks。
+    #[argh(option)]
+    sink: Vec<Target>,
+
+    /// 新合约需要超过当前主力合约的比例，默认为 0，如 `--threshold 0.1` 表示多出 10%。
+    #[argh(option, default = "0.0")]
+    threshold: f64,
+
+    /// 新合约需要连续领先的交易日数，默认为 1。
+    #[argh(option, default = "1")]
+    confirm: u32,
+
+    /// 起始日期，如 `--from 2023-01-01`。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+
+    /// 结束日期，如 `--to 2023-12-31`。
+    #[argh(option, from_str_fn(parse_date))]
+    to: Option<Date>,
+}
+
+/// 未指定 `--sink` 时按照配置中的 sinks
+fn targets(sink: Vec<Target>) -> Vec<Target> {
+    if sink.is_empty() {
+        Target::from_config()
+    } else {
+        sink
+    }
+}
+
+impl Build {
+    fn run(self) -> Result<()> {
+        match self.command {
+            BuildCommand::Main(m) => {
+                let selection = ce::Selection {
+                    products: m.product,
+                    contracts: Vec::new(),
+                    from: m.from,
+                    to: m.to,
+                };
+                let opts = main_contract::Options {
+                    rank: m.by,
+                    threshold: m.threshold,
+                    confirm_days: m.confirm,
+                };
+                let series = main_contract::run(&selection, &opts, &targets(m.sink))?;
+                if event::json() {
+                    event::set_data(&serde_json::json!({
+                        "rows": series.bars.len(),
+                        "rolls": series.rolls,
+                    }));
+                } else {
+                    for roll in &series.rolls {
+                        println!(
+                            "{} {}: {} -> {}",
+                            roll.date, roll.product, roll.from, roll.to
+                        );
+                    }
+                }
+                info!(
+                    "生成了 {} 条主力合约数据，换月 {} 次",
+                    series.bars.len(),
+                    series.rolls.len()
+                );
+            }
+            BuildCommand::Continuous(c) => {
+                let selection = ce::Selection {
+                    products: c.product,
+                    contracts: Vec::new(),
+                    from: c.from,
+                    to: c.to,
+                };
+                let opts = main_contract::Options {
+                    rank: c.by,
+                    threshold: c.threshold,
+                    confirm_days: c.confirm,
+                };
+                let adjust = continuous::Adjust {
+                    method: c.method,
+                    direction: c.direction,
+                };
+                let data = continuous::run(&selection, &opts, adjust, &targets(c.sink))?;
+                info!("生成了 {} 条复权连续合约数据", data.len());
+                event::set_data(&serde_json::json!({ "rows": data.len() }));
+            }
+            BuildCommand::Index(i) => {
+                let selection = ce::Selection {
+                    products: i.product,
+                    contracts: Vec::new(),
+                    from: i.from,
+                    to: i.to,
+                };
+                let data = index::run(&selection, i.weight, &targets(i.sink))?;
+                info!("生成了 {} 条品种指数数据", data.len());
+                event::set_data(&serde_json::json!({ "rows": data.len() }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 管理 clickhouse 表结构
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "db")]
+struct Db {
+    #[argh(subcommand)]
+    command: DbCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum DbCommand {
+    Migrate(Migrate),
+    Status(Status),
+}
+
+/// 依次执行尚未执行的迁移，使已有的表结构升级到最新。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "migrate")]
+struct Migrate {}
+
+/// 显示每个迁移是否已经执行。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "status")]
+struct Status {}
+
+impl Db {
+    fn run(self) -> Result<()> {
+        match self.command {
+            DbCommand::Migrate(Migrate {}) => {
+                let done = migrate::migrate()?;
+                if done.is_empty() {
+                    info!("表结构已是最新，无需迁移");
+                }
+                if event::json() {
+                    let done: Vec<_> = done
+                        .iter()
+                        .map(|m| serde_json::json!({ "version": m.version, "name": m.name }))
+                        .collect();
+                    event::set_data(&done);
+                    return Ok(());
+                }
+                for m in done {
+                    println!("{:04}_{} 已执行", m.version, m.name);
+                }
+            }
+            DbCommand::Status(Status {}) => {
+                let applied = migrate::applied()?;
+                if event::json() {
+                    let status: Vec<_> = migrate::MIGRATIONS
+                        .iter()
+                        .map(|m| {
+                            let a = applied.iter().find(|a| a.version == m.version);
+                            serde_json::json!({
+                                "version": m.version,
+                                "name": m.name,
+                                "applied_at": a.map(|a| &a.applied_at),
+                            })
+                        })
+                        .collect();
+                    event::set_data(&status);
+                    return Ok(());
+                }
+                for m in migrate::MIGRATIONS {
+                    match applied.iter().find(|a| a.version == m.version) {
+                        Some(a) => {
+                            println!("{:04}_{}\t已执行\t{}", m.version, m.name, a.applied_at)
+                        }
+                        None => println!("{:04}_{}\t未执行", m.version, m.name),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// 大连交易所
@@ -36,91 +666,503 @@ struct Dce {
     #[argh(switch)]
     with_options: bool,
 
-    /// 年份：xxxx 年或者 xxxx..xxxx 年。如 `-y 2022` 或者等价的 `-y 2022..2023`。
+    /// 年份（从 2006 年至下载链接中最新的年份）：xxxx、xxxx..xxxx、xxxx..=xxxx、xxxx..、..xxxx，可用逗号组合。
+    /// 如 `-y 2022` 或者等价的 `-y 2022..2023`、`-y 2022..=2022`。
     #[argh(option, short = 'y')]
     year: Option<Year>,
 
+    /// 按交易日获取日行情（包括当年数据）：单个日期或者日期范围，
+    /// 如 `--daily 2024-05-10`、`--daily 2024-05-06..=2024-05-10`。
+    #[argh(option)]
+    daily: Option<Days>,
+
     #[argh(positional, greedy)]
     kinds: Vec<Str>,
+
+    #[argh(subcommand)]
+    command: Option<DceCommand>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum DceCommand {
+    Links(Links),
+    Dsp(DceDsp),
+}
+
+/// 获取大连交易所的交割结算价（单独发布，不在年数据和日行情中），如 `dce dsp -y 2023`。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "dsp")]
+struct DceDsp {
+    /// 年份：xxxx、xxxx..xxxx、xxxx..=xxxx、xxxx..、..xxxx，可用逗号组合。
+    #[argh(option, short = 'y')]
+    year: Year,
 }
 
+/// 管理大连交易所的下载链接
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "links")]
+struct Links {
+    #[argh(subcommand)]
+    command: LinksCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum LinksCommand {
+    Refresh(Refresh),
+}
+
+/// 从大连交易所的历史数据页面获取最新的下载链接，保存到缓存目录，并显示新增和移除的 (年份, 品种)。
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "refresh")]
+struct Refresh {}
+
 /// 郑州交易所
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand, name = "czce")]
 struct Czce {
-    /// 年份（从 2010 年开始）：xxxx 年或者 xxxx..xxxx 年。如 `-y 2022` 或者等价的 `-y 2022..2023`。
+    /// 年份（从 2010 年开始）：xxxx、xxxx..xxxx、xxxx..=xxxx、xxxx..、..xxxx，可用逗号组合。
+    /// 如 `-y 2022` 或者等价的 `-y 2022..2023`、`-y 2022..=2022`。
+    /// 不指定时，根据 `--from` 和 `--to` 确定年份。
     #[argh(option, short = 'y')]
-    year: Year,
+    year: Option<Year>,
+
+    /// 只录入该日期及之后的数据，如 `--from 2023-03-01`。
+    #[argh(option, from_str_fn(parse_date))]
+    from: Option<Date>,
+
+    /// 只录入该日期及之前的数据，如 `--to 2023-06-30`。
+    #[argh(option, from_str_fn(parse_date))]
+    to: Option<Date>,
+
+    /// 按交易日获取日行情，只录入这些交易日的数据：单个日期或者日期范围，
+    /// 如 `--daily 2023-10-19`、`--daily 2023-10-16..=2023-10-20`。
+    #[argh(option)]
+    daily: Option<Days>,
+
+    /// 品种代码，如 `MA TA SR`；不指定表示所有品种。
+    #[argh(positional, greedy)]
+    products: Vec<Str>,
+}
+
+/// 郑州交易所要获取的数据：按交易日或者按年
+enum CzceJob {
+    Daily(Days),
+    Years(Year),
+}
+
+impl Czce {
+    /// 校验参数，确定按交易日还是按年获取，以及筛选条件
+    fn job(self) -> Result<(CzceJob, czce::Filter)> {
+        let Czce {
+            year,
+            from,
+            to,
+            daily,
+            products,
+        } = self;
+        if let Some((from, to)) = from.zip(to) {
+            ensure!(from <= to, "--from {from} 晚于 --to {to}");
+        }
+        let filter = czce::Filter { from, to, products };
+        if let Some(days) = daily {
+            ensure!(year.is_none(), "--daily 与 -y 不能同时使用");
+            return Ok((CzceJob::Daily(days), filter));
+        }
+        let year = match (year, from) {
+            (Some(year), _) => year,
+            (None, Some(from)) => Year(vec![YearSpan::Range {
+                start: Some(from.year() as u16),
+                end: to.map(|to| to.year() as u16),
+                inclusive: true,
+            }]),
+            (None, None) => bail!("需要指定 -y 或者 --from"),
+        };
+        Ok((CzceJob::Years(year), filter))
+    }
+
+    fn plan(self) -> Result<Plan> {
+        let mut planner = Planner::default();
+        match self.job()? {
+            (CzceJob::Daily(days), _) => days
+                .weekdays()
+                .into_iter()
+                .for_each(|d| planner.czce_daily(d)),
+            (CzceJob::Years(year), filter) => {
+                for y in year.resolve(Exchange::czce.years())? {
+                    planner.czce(y, &filter)?;
+                }
+            }
+        }
+        Ok(planner.finish())
+    }
+
+    fn run(self) -> Result<()> {
+        let (year, filter) = match self.job()? {
+            (CzceJob::Daily(days), filter) => {
+                return days.for_each_weekday(|date| Ok(czce::run_daily(date, &filter)?));
+            }
+            (CzceJob::Years(year), filter) => (year, filter),
+        };
+        let years = year.resolve(Exchange::czce.years())?;
+        let urls = years
+            .iter()
+            .filter(|&&y| filter.contains_year(y))
+            .map(|&y| czce::get_url(y))
+            .collect::<Result<_, _>>()?;
+        let _prefetch = util::prefetch::start(urls);
+        for_each_year(Exchange::czce, years, |y| Ok(czce::run(y, &filter)?))
+    }
+}
+
+fn refresh_links() -> Result<()> {
+    let (added, removed) = dce::refresh_links()?;
+    if added.is_empty() && removed.is_empty() {
+        info!("大连交易所的下载链接没有变化");
+    }
+    if event::json() {
+        event::set_data(&serde_json::json!({ "added": added, "removed": removed }));
+        return Ok(());
+    }
+    for key in added {
+        println!("+ {key}");
+    }
+    for key in removed {
+        println!("- {key}");
+    }
+    Ok(())
+}
+
+/// 日期：`2024-05-10`、`2024-05-06..2024-05-11` 或者 `2024-05-06..=2024-05-10`
+#[derive(Debug, PartialEq, Eq)]
+struct Days {
+    start: Date,
+    /// 包含该日
+    end: Date,
+}
+
+impl std::str::FromStr for Days {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let days = match s.split_once("..") {
+            Some((start, end)) => {
+                let start = parse_date(start)?;
+                let end = match end.strip_prefix('=') {
+                    Some(end) => parse_date(end)?,
+                    None => parse_date(end)?
+                        .previous_day()
+                        .ok_or_else(|| format!("{s} 的结束日期无效"))?,
+                };
+                Days { start, end }
+            }
+            None => {
+                let date = parse_date(s)?;
+                Days {
+                    start: date,
+                    end: date,
+                }
+            }
+        };
+        if days.start > days.end {
+            return Err(format!("{s} 不包含任何日期"));
+        }
+        Ok(days)
+    }
+}
+
+impl Days {
+    /// 跳过周末；节假日由交易所返回空数据
+    fn weekdays(&self) -> Vec<Date> {
+        let mut days = Vec::new();
+        let mut date = self.start;
+        while date <= self.end {
+            if !matches!(date.weekday(), Weekday::Saturday | Weekday::Sunday) {
+                days.push(date);
+            }
+            date = match date.next_day() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        days
+    }
+
+    fn for_each_weekday(self, mut f: impl FnMut(Date) -> Result<()>) -> Result<()> {
+        let days = self.weekdays();
+        let overall = progress::overall(days.len(), "交易日");
+        for date in days {
+            f(date)?;
+            overall.inc();
+        }
+        Ok(())
+    }
+}
+
+/// 解析 `YYYY-MM-DD` 格式的日期
+fn parse_date(s: &str) -> Result<Date, String> {
+    const FMT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
+    Date::parse(s, FMT).map_err(|err| format!("{s} 不是 YYYY-MM-DD 格式的日期：{err}"))
 }
 
 impl Args {
+    pub fn output(&self) -> Output {
+        self.output
+    }
+
+    /// 运行子命令；以 JSON 输出时，最后输出 `result` 事件
     pub fn run(self) -> Result<()> {
+        event::set_output(self.output);
+        let command = self.command.name();
+        let started = std::time::Instant::now();
+        let res = self.run_command(started);
+        let error = res.as_ref().err().map(|err| ErrorInfo {
+            kind: err
+                .downcast_ref::<Error>()
+                .map_or("Other", Error::kind)
+                .to_owned(),
+            message: format!("{err:#}"),
+        });
+        if let Some(error) = &error {
+            event::emit(&Event::Error(error.clone()));
+        }
+        event::emit(&Event::Result {
+            command,
+            ok: error.is_none(),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            data: event::take_data(),
+            error,
+        });
+        res
+    }
+
+    fn run_command(self, started: std::time::Instant) -> Result<()> {
         debug!("Args = {self:?}");
-        match self.exchange {
-            Exchange::Czce(Czce { year }) => year.for_each_year(czce::run)?,
-            Exchange::Dce(d) => {
+        let mut config = util::Config::load(self.config.as_deref())?;
+        if let Some(jobs) = self.jobs {
+            ensure!(jobs != 0, "--jobs 至少为 1");
+            config.network.jobs = jobs;
+        }
+        util::set_config(config)?;
+        if self.dry_run {
+            return dry_run(self.command);
+        }
+        match self.command {
+            Command::Czce(czce) => czce.run()?,
+            // 只管理表结构，不重新录入
+            Command::Db(db) => return db.run(),
+            // 只读取 qihuo.ce，不重新录入
+            Command::Build(build) => return build.run(),
+            Command::Resample(resample) => return resample.run(),
+            Command::Curve(curve) => return curve.run(),
+            Command::Query(query) => return query.run(),
+            Command::Status(status) => return status.run(),
+            Command::Dce(Dce {
+                command: Some(DceCommand::Links(Links { command })),
+                ..
+            }) => {
+                match command {
+                    LinksCommand::Refresh(Refresh {}) => refresh_links()?,
+                }
+                // 只更新链接，不重新录入
+                return Ok(());
+            }
+            Command::Dce(Dce {
+                command: Some(DceCommand::Dsp(DceDsp { year })),
+                ..
+            }) => for_each_year(Exchange::dce, year.resolve(Exchange::dce.years())?, |y| {
+                Ok(dce::run_dsp(y)?)
+            })?,
+            Command::Dce(Dce {
+                daily: Some(days),
+                year,
+                kinds,
+                ..
+            }) => {
+                ensure_daily_only(year.as_ref(), &kinds)?;
+                days.for_each_weekday(|date| Ok(dce::run_daily(date)?))?
+            }
+            Command::Dce(d) => {
                 if d.select || (d.year.is_none() && d.kinds.is_empty()) {
                     if dce::select(d.with_options)?.is_none() {
                         // None 表示被中断，不重新录入
                         return Ok(());
                     }
                 } else if let Some(year) = d.year {
-                    year.for_each_year(|y| {
-                        for kind in &d.kinds {
-                            let link = dce::get_url(y, kind)?;
-                            info!("{link}");
-                        }
-                        Ok(())
-                    })?;
+                    let years = year.resolve(dce::link_years())?;
+                    info!("dce 年份：{years:?}");
+                    let (keys, urls): (Vec<_>, Vec<_>) = dce_links(&years, &d.kinds)?
+                        .into_iter()
+                        .map(|(y, kind, url)| ((y, kind), url))
+                        .unzip();
+                    let _prefetch = util::prefetch::start(urls);
+                    let overall = progress::overall(keys.len(), "dce (年份, 品种)");
+                    for (y, kind) in keys {
+                        dce::run(y, kind)?;
+                        overall.inc();
+                    }
                 }
             }
         }
         // 重新录入 qihuo.ce
         ce::run()?;
+        print_summary(started.elapsed());
         Ok(())
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Year {
-    Single(u16),
-    /// start..end
-    Range {
-        start: u16,
-        end: u16,
-    },
+/// `dce --daily` 获取所有品种的日行情，与 `-y` 和品种不能同时使用
+fn ensure_daily_only(year: Option<&Year>, kinds: &[Str]) -> Result<()> {
+    ensure!(year.is_none(), "--daily 与 -y 不能同时使用");
+    ensure!(
+        kinds.is_empty(),
+        "--daily 获取所有品种的日行情，不能指定品种 {kinds:?}"
+    );
+    Ok(())
 }
 
-impl std::str::FromStr for Year {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let pattern = r"^((?P<range>(?P<start>\d{4})\.\.(?P<end>\d{4}))|(?P<single>\d{4}))$";
-        let re = Regex::new(pattern).unwrap();
-        let cap = re
-            .captures(s)
-            .ok_or_else(|| format!(r"{s} 不是年份，应输入 \d{{4}} 或者 \d{{4}}..\d{{4}}"))?;
-        let parse = |key: &str| {
-            let res = cap.name(key).unwrap().as_str().parse::<u16>();
-            res.map_err(|err| format!("{s} 无法解析为 u16: {err}"))
-        };
-        let year = if cap.name("range").is_some() {
-            Year::Range {
-                start: parse("start")?,
-                end: parse("end")?,
+/// `dce -y` 的 (年份, 品种, 下载链接)：下载链接中没有的 (年份, 品种) 被跳过，
+/// 因为开放的年份范围可能包含品种上市之前或者退市之后的年份；都没有时返回错误
+fn dce_links<'a>(years: &[u16], kinds: &'a [Str]) -> Result<Vec<(u16, &'a str, String)>> {
+    let mut links = Vec::with_capacity(years.len() * kinds.len());
+    for &y in years {
+        for kind in kinds {
+            match dce::get_url(y, kind) {
+                Ok(url) => links.push((y, kind.as_str(), url)),
+                Err(err) => warn!("跳过：{err}"),
             }
-        } else {
-            Year::Single(parse("single")?)
-        };
-        Ok(year)
+        }
     }
+    ensure!(
+        !links.is_empty(),
+        "下载链接中没有 {years:?} 年的 {kinds:?}，可以先运行 `ce dce links refresh`"
+    );
+    Ok(links)
 }
 
-impl Year {
-    fn for_each_year(self, mut f: impl FnMut(u16) -> Result<()>) -> Result<()> {
-        match self {
-            Year::Single(year) => f(year),
-            Year::Range { start, end } => (start..end).try_for_each(f),
+/// 试运行：生成计划并显示，不联网、不访问数据库
+fn dry_run(command: Command) -> Result<()> {
+    let plan = match command {
+        Command::Czce(czce) => czce.plan()?,
+        Command::Dce(Dce {
+            command: Some(DceCommand::Links(_)),
+            ..
+        }) => plan::links_refresh(),
+        Command::Dce(Dce {
+            command: Some(DceCommand::Dsp(DceDsp { year })),
+            ..
+        }) => {
+            let mut planner = Planner::default();
+            for y in year.resolve(Exchange::dce.years())? {
+                planner.dce_dsp(y);
+            }
+            planner.finish()
+        }
+        Command::Dce(Dce {
+            daily: Some(days),
+            year,
+            kinds,
+            ..
+        }) => {
+            ensure_daily_only(year.as_ref(), &kinds)?;
+            let mut planner = Planner::default();
+            for date in days.weekdays() {
+                planner.dce_daily(date);
+            }
+            planner.finish()
+        }
+        Command::Dce(Dce {
+            select: false,
+            year: Some(year),
+            kinds,
+            ..
+        }) if !kinds.is_empty() => {
+            let mut planner = Planner::default();
+            for (y, kind, _) in dce_links(&year.resolve(dce::link_years())?, &kinds)? {
+                planner.dce(y, kind)?;
+            }
+            planner.finish()
         }
+        Command::Dce(_) => bail!("--dry-run 不支持交互式选择，请使用 -y 指定年份并列出品种"),
+        Command::Db(Db {
+            command: DbCommand::Migrate(_),
+        }) => Plan {
+            sql: plan::migrations(),
+            ..Plan::default()
+        },
+        _ => bail!("--dry-run 只适用于 czce、dce 和 db migrate"),
+    };
+    if event::json() {
+        event::set_data(&plan);
+    } else {
+        print!("{}", plan.to_text());
+    }
+    Ok(())
+}
+
+/// 录入的表；出错时为“出错”
+fn table_or_error(e: &util::manifest::Entry) -> &str {
+    match (&e.table, &e.error) {
+        (_, Some(_)) => "出错",
+        (Some(table), None) => table,
+        (None, None) => "-",
+    }
+}
+
+/// 显示本次运行中每个下载文件的解析、录入、去重的行数和耗时
+fn print_summary(elapsed: std::time::Duration) {
+    let entries = util::manifest::finished();
+    if event::json() {
+        event::set_data(&serde_json::json!({
+            "files": entries,
+            "elapsed_ms": elapsed.as_millis() as u64,
+        }));
+        return;
+    }
+    if entries.is_empty() {
+        return;
+    }
+    let opt = |n: Option<u64>| n.map_or_else(|| "-".to_owned(), |n| n.to_string());
+    let secs = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+    println!("文件\t表\t解析\t跳过\t录入\t去重\t耗时");
+    for e in &entries {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            progress::file_name(&e.url),
+            table_or_error(e),
+            e.parsed,
+            e.rejected,
+            opt(e.inserted),
+            opt(e.deduplicated),
+            secs(e.elapsed_ms),
+        );
+    }
+    let sum = |f: fn(&util::manifest::Entry) -> Option<u64>| {
+        entries.iter().filter_map(f).reduce(|a, b| a + b)
+    };
+    println!(
+        "合计 {} 个文件\t-\t{}\t{}\t{}\t{}\t{}",
+        entries.len(),
+        opt(sum(|e| Some(e.parsed))),
+        opt(sum(|e| Some(e.rejected))),
+        opt(sum(|e| e.inserted)),
+        opt(sum(|e| e.deduplicated)),
+        secs(elapsed.as_millis() as u64),
+    );
+}
+
+/// 依次处理每年，并显示总体进度
+fn for_each_year(
+    exchange: Exchange,
+    years: Vec<u16>,
+    mut f: impl FnMut(u16) -> Result<()>,
+) -> Result<()> {
+    info!("{exchange} 年份：{years:?}");
+    let overall = progress::overall(years.len(), &format!("{exchange} 年份"));
+    for year in years {
+        f(year)?;
+        overall.inc();
     }
+    Ok(())
 }
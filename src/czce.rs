@@ -1,8 +1,15 @@
-use crate::{util, Result, Str};
-use serde::Deserialize;
-use time::Date;
+use crate::{
+    error::Context,
+    util::{self, clickhouse},
+    Error, Exchange, Result, Str,
+};
+use serde::{Deserialize, Serialize};
+use std::io;
+use time::{format_description::FormatItem, macros::format_description, Date};
 
 const MEMO: &str = "自2020年1月1日起，成交量、持仓量、成交额、行权量均为单边计算";
+/// 从该日起，成交量、持仓量、成交额为单边计算；之前为双边
+pub const SINGLE_SIDED_SINCE: Date = time::macros::date!(2020 - 01 - 01);
 
 /// 注意：
 /// 2010..=2014 使用 http://www.czce.com.cn/cn/exchange/datahistory2010.zip
@@ -18,12 +25,18 @@ pub fn get_url(year: u16) -> Result<String> {
         2020.. if year <= this_year => {
             format!("http://www.czce.com.cn/cn/DFSStaticFiles/Future/{year}/ALLFUTURES{year}.zip")
         }
-        _ => bail!("{year} 必须在 2010..={this_year} 范围内"),
+        _ => {
+            return Err(Error::UnsupportedYear {
+                year,
+                min: Exchange::czce.first_year(),
+                max: this_year,
+            })
+        }
     };
     Ok(url)
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[cfg_attr(feature = "tabled", derive(tabled::Tabled))]
 pub struct Data {
     /// 交易日期
@@ -79,7 +92,44 @@ pub struct Data {
     pub dsp: Option<f32>,
 }
 
-pub fn run(year: u16) -> Result<()> {
+/// 录入前对数据行的筛选：日期范围（包含两端）和品种代码
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Filter {
+    /// 起始日期
+    pub from: Option<Date>,
+    /// 结束日期
+    pub to: Option<Date>,
+    /// 品种代码，如 `MA`、`TA`（不区分大小写）；为空表示所有品种
+    pub products: Vec<Str>,
+}
+
+impl Filter {
+    pub fn matches(&self, data: &Data) -> bool {
+        let date = data.date;
+        self.from.is_none_or(|from| from <= date)
+            && self.to.is_none_or(|to| date <= to)
+            && (self.products.is_empty() || {
+                let product = util::product(&data.code);
+                self.products
+                    .iter()
+                    .any(|p| p.eq_ignore_ascii_case(product))
+            })
+    }
+
+    /// 该年是否可能有符合日期范围的数据
+    pub fn contains_year(&self, year: u16) -> bool {
+        let year = i32::from(year);
+        self.from.is_none_or(|from| from.year() <= year)
+            && self.to.is_none_or(|to| year <= to.year())
+    }
+}
+
+pub fn run(year: u16, filter: &Filter) -> Result<()> {
+    let _manifest = util::manifest::guard();
+    if !filter.contains_year(year) {
+        info!("{year} 年不在 {filter:?} 的日期范围内，跳过");
+        return Ok(());
+    }
     // NOTE: GBK 编码的表头与现有 UTF8 的表头和内容不一致：
     // * 空盘量（GBK） -> 持仓量（UTF8)
     // * 换行符是 CRLF -> LF
@@ -89,61 +139,198 @@ pub fn run(year: u16) -> Result<()> {
     // TODO: 该函数返回一个状态来在录入当年数据后替换 dsp 的 SQL 语句
     util::fetch_zip(&get_url(year)?, |raw, fname| {
         let (txt, encoding) = util::read_txt(&raw, &fname)?;
-        let csv_content = parse_txt(&txt, None::<fn(_) -> _>)?;
+        let csv_content = to_csv(deserialize(strip_txt(&txt, 2).as_bytes()), filter)?;
         let fname = format!("czce-{fname}");
-        util::save_to_csv_and_clickhouse(
-            || util::save_csv(csv_content.trim().as_bytes(), fname),
-            || {
-                util::clickhouse::execute(include_str!("./sql/czce.sql"))?;
-                const TABLE: &str = "qihuo.czce";
-                util::clickhouse::insert_with_count_reported(TABLE, csv_content.as_bytes())?;
-                if matches!(encoding, util::Encoding::GBK) {
-                    util::clickhouse::execute(&format!(
-                        "ALTER TABLE qihuo.czce UPDATE dsp=Null \
-                         WHERE dsp==0 AND year(date)=={year};"
-                    ))?;
-                    info!("{TABLE} 由于源数据不规范，需要将 dsp 为 0 的数据修改为 Null");
-                }
-                Ok(())
-            },
-        )?;
+        save(&csv_content, &fname, encoding, &year_scope(year))?;
         info!("成功获取 {year} 年的数据\n来自【郑州交易所】的数据备注：{MEMO}");
         Ok(())
-    })
+    })?;
+    util::manifest::finish(Some(&clickhouse::table("czce")))?;
+    Ok(())
+}
+
+/// 每日行情文件：每个交易日收盘后发布，非交易日不存在
+pub fn get_daily_url(date: Date) -> String {
+    const FMT: &[FormatItem<'static>] = format_description!("[year][month][day]");
+    // Date 的格式化只在格式无效时出错
+    let ymd = date.format(FMT).unwrap();
+    format!(
+        "http://www.czce.com.cn/cn/DFSStaticFiles/Future/{}/{ymd}/FutureDataDaily.txt",
+        date.year()
+    )
+}
+
+/// 逐行解析某交易日的日行情文本。
+///
+/// 与年数据共用表头和千位分隔符的处理；日行情没有交易日期列，且包含小计和总计行，
+/// 所以先去掉这些行，并在每行前加上交易日期。
+pub fn daily_rows(raw: &str, date: Date) -> impl Iterator<Item = Result<Data>> {
+    let stripped = strip_txt(raw, 1);
+    let mut txt = String::with_capacity(stripped.len() * 2);
+    let mut lines = stripped.lines();
+    if let Some(header) = lines.next() {
+        txt.push_str("交易日期|");
+        txt.push_str(header);
+        txt.push('\n');
+    }
+    for line in lines.filter(|l| !(l.contains("小计") || l.contains("总计"))) {
+        txt.push_str(&format!("{date}|{line}\n"));
+    }
+    deserialize(io::Cursor::new(txt.into_bytes()))
+}
+
+/// 下载并逐行解析某交易日所有合约的数据，不涉及文件和数据库。非交易日没有数据。
+pub fn fetch_daily(date: Date) -> impl Iterator<Item = Result<Data>> {
+    util::try_iter(fetch_daily_txt(date).map(|txt| {
+        txt.into_iter()
+            .flat_map(move |(txt, _)| daily_rows(&txt, date))
+    }))
+}
+
+fn fetch_daily_txt(date: Date) -> Result<Option<(String, util::Encoding)>> {
+    let url = get_daily_url(date);
+    let Some(raw) = util::fetch_if_exists(&url)? else {
+        return Ok(None);
+    };
+    let (txt, encoding) = util::read_txt(raw.get_ref(), &url)?;
+    Ok(Some((txt.into_owned(), encoding)))
+}
+
+/// 只录入某交易日的数据：下载日行情文件，而不是整年的 zip 文件
+pub fn run_daily(date: Date, filter: &Filter) -> Result<()> {
+    let _manifest = util::manifest::guard();
+    let Some((txt, encoding)) = fetch_daily_txt(date)? else {
+        info!("{date} 没有郑州交易所的日行情数据（或许不是交易日）");
+        return Ok(());
+    };
+    let csv_content = to_csv(daily_rows(&txt, date), filter)?;
+    save(
+        &csv_content,
+        &format!("czce-daily-{date}"),
+        encoding,
+        &daily_scope(date),
+    )?;
+    util::manifest::finish(Some(&clickhouse::table("czce")))?;
+    info!("成功获取 {date} 的数据\n来自【郑州交易所】的数据备注：{MEMO}");
+    Ok(())
+}
+
+/// GBK 编码的源文件中没有交割结算价时为 0：录入后把 `scope` 范围内 dsp 为 0 的数据修改为 Null
+pub fn dsp_fixup(table: &str, scope: &str) -> String {
+    format!("ALTER TABLE {table} UPDATE dsp=Null WHERE dsp==0 AND {scope};")
+}
+
+/// 年数据录入的范围，见 [`dsp_fixup`]
+pub fn year_scope(year: u16) -> String {
+    format!("year(date)=={year}")
+}
+
+/// 日行情录入的范围，见 [`dsp_fixup`]
+pub fn daily_scope(date: Date) -> String {
+    format!("date=='{date}'")
+}
+
+/// 保存 csv 文件，并录入到 qihuo.czce；`scope` 为本次录入数据的 SQL 条件，用于修正 dsp
+fn save(csv_content: &[u8], fname: &str, encoding: util::Encoding, scope: &str) -> Result<()> {
+    util::save_to_csv_and_clickhouse(
+        || util::save_csv(csv_content, fname),
+        || {
+            util::migrate::migrate_once()?;
+            let table = clickhouse::table("czce");
+            let inserted = clickhouse::insert_with_count_reported(&table, csv_content)?;
+            if matches!(encoding, util::Encoding::GBK) {
+                clickhouse::execute(&dsp_fixup(&table, scope))?;
+                info!("{table} 由于源数据不规范，需要将 dsp 为 0 的数据修改为 Null");
+            }
+            Ok(inserted)
+        },
+    )
+}
+
+/// 筛选数据行，并写成无表头的 csv，用于保存和录入。无法解析的行会被记录并跳过。
+fn to_csv(rows: impl Iterator<Item = Result<Data>>, filter: &Filter) -> Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::with_capacity(1024 * 1024));
+    let (mut parsed, mut rejected, mut kept) = (0usize, 0usize, 0usize);
+    for data in rows {
+        match data {
+            Ok(data) => {
+                parsed += 1;
+                if filter.matches(&data) {
+                    writer
+                        .serialize(&data)
+                        .or_err(Error::Row, || format!("{data:?} 无法写入 csv"))?;
+                    kept += 1;
+                }
+            }
+            Err(err) => {
+                rejected += 1;
+                error!("{err}");
+            }
+        }
+    }
+    info!("解析了 {parsed} 条数据（另有 {rejected} 条无法解析），筛选后保留 {kept} 条");
+    util::manifest::count(parsed as u64, rejected as u64);
+    writer
+        .into_inner()
+        .map_err(|err| Error::Io(err.into_error()))
 }
 
 pub fn parse_txt(raw: &str, f: Option<impl FnMut(Data)>) -> Result<String> {
+    let stripped = strip_txt(raw, 2);
+    let Some(f) = f else { return Ok(stripped) };
+    deserialize(stripped.as_bytes())
+        .filter_map(|data| data.map_err(|err| error!("{err}")).ok())
+        .for_each(f);
+    Ok(stripped)
+}
+
+/// 逐行解析郑州交易所的年数据 txt 文件（UTF8 或者 GBK 编码）。
+///
+/// 读取或解码失败时，迭代器只产生一个错误。
+pub fn rows(reader: impl io::Read) -> impl Iterator<Item = Result<Data>> {
+    fn stripped(mut reader: impl io::Read) -> Result<String> {
+        let mut raw = Vec::with_capacity(1024 * 1024);
+        reader.read_to_end(&mut raw)?;
+        let (txt, _) = util::read_txt(&raw, "czce")?;
+        Ok(strip_txt(&txt, 2))
+    }
+    util::try_iter(stripped(reader).map(|s| deserialize(io::Cursor::new(s.into_bytes()))))
+}
+
+/// 下载并逐行解析某年所有合约的数据，不涉及文件和数据库。
+pub fn fetch_year(year: u16) -> impl Iterator<Item = Result<Data>> {
+    let files = get_url(year).and_then(|url| util::unzip(&url));
+    util::try_iter(files.map(|files| {
+        files
+            .into_iter()
+            .flat_map(|(raw, _)| rows(io::Cursor::new(raw)))
+    }))
+}
+
+/// 跳过表头之前的 `skip` 行，并删除所有数字千位分隔符和单元格内的空格
+fn strip_txt(raw: &str, skip: usize) -> String {
     let mut start = 0;
-    // 跳过前两行
-    for head in raw.lines().take(2) {
-        info!("{head}");
+    for head in raw.split_inclusive('\n').take(skip) {
+        info!("{}", head.trim());
         start += head.len();
     }
-    start += 2;
-    // 删除所有数字千位分隔符和单元格内的空格
-    let stripped = util::init_data()
+    util::init_data()
         .regex_czce
         .replace_all(raw[start..].trim(), "")
-        .into_owned();
-    let Some(f) = f else { return Ok(stripped) };
-    let mut reader = csv::ReaderBuilder::new()
+        .into_owned()
+}
+
+/// 以 `|` 分隔、首行为表头的文本
+fn deserialize(reader: impl io::Read) -> impl Iterator<Item = Result<Data>> {
+    csv::ReaderBuilder::new()
         .delimiter(b'|')
-        .from_reader(stripped.as_bytes());
-    reader
-        .records()
-        .filter_map(|record| match &record {
-            Ok(line) => match line.deserialize::<Data>(None) {
-                Ok(data) => Some(data),
-                Err(err) => {
-                    error!("反序列化 {line:?} 出错：{err:?}");
-                    None
-                }
-            },
-            Err(err) => {
-                error!("解析 {record:?} 出错：{err:?}");
-                None
-            }
+        .from_reader(reader)
+        .into_records()
+        .map(|record| {
+            let line = record.or_err(Error::Row, || "无法解析 csv 行".into())?;
+            line.deserialize::<Data>(None)
+                .or_err(Error::Row, || format!("反序列化 {line:?} 出错"))
         })
-        .for_each(f);
-    Ok(stripped)
 }
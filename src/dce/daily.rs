@@ -0,0 +1,167 @@
+use super::{save, Context, Data, Date, Error, Result, Str};
+use crate::util;
+use std::collections::HashMap;
+
+/// 日行情导出接口（POST 表单）：每个交易日所有合约的行情，包括当年数据
+pub const DAILY_URL: &str = "http://www.dce.com.cn/publicweb/quotesdata/exportDayQuotesChData.html";
+
+/// 品种名称与合约代码前缀：日行情只有品种名称和交割月份，需要拼接成合约代码（与年数据一致，为小写）
+pub const PRODUCTS: &[(&str, &str)] = &[
+    ("豆一", "a"),
+    ("豆二", "b"),
+    ("豆粕", "m"),
+    ("豆油", "y"),
+    ("棕榈油", "p"),
+    ("玉米", "c"),
+    ("玉米淀粉", "cs"),
+    ("鸡蛋", "jd"),
+    ("粳米", "rr"),
+    ("生猪", "lh"),
+    ("原木", "lg"),
+    ("纤维板", "fb"),
+    ("胶合板", "bb"),
+    ("聚乙烯", "l"),
+    ("聚氯乙烯", "v"),
+    ("聚丙烯", "pp"),
+    ("苯乙烯", "eb"),
+    ("乙二醇", "eg"),
+    ("液化石油气", "pg"),
+    ("焦炭", "j"),
+    ("焦煤", "jm"),
+    ("铁矿石", "i"),
+];
+
+/// 日行情接口 [`DAILY_URL`] 的表单
+pub fn daily_form(date: Date) -> [(&'static str, String); 6] {
+    [
+        ("dayQuotes.variety", "all".into()),
+        ("dayQuotes.trade_type", "0".into()),
+        ("year", date.year().to_string()),
+        // 接口中的月份从 0 开始
+        ("month", (u8::from(date.month()) - 1).to_string()),
+        ("day", date.day().to_string()),
+        ("exportFlag", "txt".into()),
+    ]
+}
+
+/// 下载某交易日所有期货合约的日行情。非交易日没有数据。
+pub fn fetch_daily(date: Date) -> Result<Vec<Data>> {
+    let form = daily_form(date);
+    let form = form.each_ref().map(|(k, v)| (*k, v.as_str()));
+    let raw = util::fetch_form(DAILY_URL, &form)?;
+    let (txt, _) = util::read_txt(raw.get_ref(), &format!("{DAILY_URL} ({date})"))?;
+    parse_daily(&txt, date)
+}
+
+/// 解析日行情文本：表头之前的说明行、小计和总计行会被跳过；数字可能带有千位分隔符。
+///
+/// 日行情的成交额单位为万元，转换成与年数据一致的元。
+pub fn parse_daily(txt: &str, date: Date) -> Result<Vec<Data>> {
+    let mut lines = txt.lines().map(str::trim).filter(|l| !l.is_empty());
+    let header = lines
+        .by_ref()
+        .find(|l| l.contains("商品名称") && l.contains("交割月份"))
+        .or_err(Error::Header, || format!("{date} 的日行情中找不到表头"))?;
+    let cols: Vec<_> = header.split_whitespace().collect();
+    // 表头可能带有单位，如 `成交额(万元)`
+    let pos = |name: &str| {
+        cols.iter()
+            .position(|c| c.split(['(', '（']).next() == Some(name))
+            .or_err(Error::Header, || format!("日行情缺少 {name} 列：{cols:?}"))
+    };
+    let [name, month, open, high, low, close, prev, settle, zd1, zd2, vol, position, amount] = [
+        "商品名称",
+        "交割月份",
+        "开盘价",
+        "最高价",
+        "最低价",
+        "收盘价",
+        "前结算价",
+        "结算价",
+        "涨跌",
+        "涨跌1",
+        "成交量",
+        "持仓量",
+        "成交额",
+    ]
+    .map(pos);
+    let pos = [
+        name?, month?, open?, high?, low?, close?, prev?, settle?, zd1?, zd2?, vol?, position?,
+        amount?,
+    ];
+    let products: HashMap<_, _> = PRODUCTS.iter().copied().collect();
+    let regex = &util::init_data().regex_czce;
+    let mut v = Vec::with_capacity(512);
+    for line in lines {
+        // 小计、总计行的列数与表头不同
+        if line.contains("小计") || line.contains("总计") {
+            continue;
+        }
+        let cells: Vec<_> = line.split_whitespace().collect();
+        if cells.len() != cols.len() {
+            error!("{date} 的日行情无法解析 {line:?}：列数与表头 {cols:?} 不一致");
+            util::manifest::count(0, 1);
+            continue;
+        }
+        let product = cells[pos[0]];
+        let cell = |i: usize| regex.replace_all(cells[pos[i]], "");
+        let float = |i: usize| -> Result<f64> {
+            let s = cell(i);
+            // 无成交时开盘价等为 `-`
+            if s == "-" || s.is_empty() {
+                return Ok(0.0);
+            }
+            s.parse().or_err(Error::Row, || {
+                format!("{line:?} 的 {} 无法解析为数字", cols[pos[i]])
+            })
+        };
+        let Some(code) = products.get(product) else {
+            warn!("{date} 的日行情出现未知品种 {product}，跳过 {line:?}");
+            util::manifest::count(0, 1);
+            continue;
+        };
+        v.push(Data {
+            code: Str::from(format!("{code}{}", cells[pos[1]])),
+            date,
+            prev: float(6)? as f32,
+            open: float(2)? as f32,
+            high: float(3)? as f32,
+            low: float(4)? as f32,
+            close: float(5)? as f32,
+            settle: float(7)? as f32,
+            zd1: float(8)? as f32,
+            zd2: float(9)? as f32,
+            vol: float(10)? as u32,
+            amount: (float(12)? * 10000.0).round() as u64,
+            position: float(11)? as u32,
+            // 日行情没有前收盘价
+            prev_close: None,
+        });
+    }
+    Ok(v)
+}
+
+/// 下载某交易日的日行情，保存到 csv 并录入 qihuo.dce
+pub fn run_daily(date: Date) -> Result<()> {
+    let _manifest = util::manifest::guard();
+    let data = fetch_daily(date)?;
+    if data.is_empty() {
+        info!("{date} 没有大连交易所的日行情数据（或许不是交易日）");
+        util::manifest::finish(None)?;
+        return Ok(());
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::with_capacity(data.len() * 128));
+    for row in &data {
+        writer
+            .serialize(row)
+            .or_err(Error::Row, || format!("{row:?} 无法写入 csv"))?;
+    }
+    writer.flush()?;
+    save(writer.get_ref(), &format!("dce-daily-{date}.csv"))?;
+    util::manifest::count(data.len() as u64, 0);
+    util::manifest::finish(Some(&util::clickhouse::table("dce")))?;
+    info!("成功获取大连交易所 {date} 的 {} 条日行情数据", data.len());
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use super::{Context, Date, Error, Result, Str};
+use crate::util;
+use serde::Serialize;
+use time::{format_description::FormatItem, macros::format_description};
+
+/// 交割结算价查询页面（POST 表单）：按月份范围返回所有品种的交割结算价表格
+pub const DSP_URL: &str = "http://www.dce.com.cn/publicweb/quotesdata/deliverySettlePrice.html";
+
+/// 交割结算价：与 `qihuo.dce_dsp` 的列一一对应
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "tabled", derive(tabled::Tabled))]
+pub struct Dsp {
+    /// 合约代码（小写，与年数据一致）
+    pub code: Str,
+    /// 交割日期：合并到 `qihuo.ce` 时，交割结算价放在合约的最后一个交易日
+    pub date: Date,
+    /// 交割结算价
+    pub dsp: f32,
+}
+
+/// 交割结算价查询页面 [`DSP_URL`] 的表单：某年所有品种
+pub fn dsp_form(year: u16) -> [(&'static str, String); 3] {
+    [
+        ("deliverySettlePriceQuotes.variety", "all".into()),
+        ("deliverySettlePriceQuotes.begin_month", format!("{year}01")),
+        ("deliverySettlePriceQuotes.end_month", format!("{year}12")),
+    ]
+}
+
+/// 下载某年所有品种的交割结算价
+pub fn fetch_dsp(year: u16) -> Result<Vec<Dsp>> {
+    let form = dsp_form(year);
+    let form = form.each_ref().map(|(k, v)| (*k, v.as_str()));
+    let raw = util::fetch_form(DSP_URL, &form)?;
+    let (html, _) = util::read_txt(raw.get_ref(), &format!("{DSP_URL} ({year})"))?;
+    parse_dsp(&html)
+}
+
+/// 解析交割结算价页面中的表格：通过表头确定合约、交割日期、交割结算价所在的列。
+pub fn parse_dsp(html: &str) -> Result<Vec<Dsp>> {
+    let dom =
+        tl::parse(html, Default::default()).or_err(Error::Header, || "无法解析 HTML".into())?;
+    let parser = dom.parser();
+    let mut rows = dom
+        .query_selector("tr")
+        .or_err(Error::Header, || "无法搜索 tr 标签".into())?
+        .filter_map(|tr| tr.get(parser)?.as_tag())
+        .map(|tr| {
+            tr.children()
+                .top()
+                .iter()
+                .filter_map(|cell| cell.get(parser)?.as_tag())
+                .filter(|cell| matches!(&*cell.name().as_utf8_str(), "th" | "td"))
+                .map(|cell| cell.inner_text(parser).trim().to_owned())
+                .collect::<Vec<_>>()
+        });
+    let header = rows
+        .by_ref()
+        .find(|cells| cells.iter().any(|c| c.contains("交割结算价")))
+        .or_err(Error::Header, || "交割结算价页面中找不到表头".into())?;
+    let pos = |name: &str| {
+        header
+            .iter()
+            .position(|c| c.contains(name))
+            .or_err(Error::Header, || {
+                format!("交割结算价缺少 {name} 列：{header:?}")
+            })
+    };
+    let (code, date, dsp) = (pos("合约")?, pos("交割日期")?, pos("交割结算价")?);
+    const FMT: &[FormatItem<'static>] = format_description!("[year][month][day]");
+    let regex = &util::init_data().regex_czce;
+    let mut v = Vec::with_capacity(256);
+    for cells in rows {
+        if cells.len() != header.len() {
+            // 空表时为“暂无数据”之类的单元格
+            continue;
+        }
+        let date_str = &cells[date];
+        let date_parsed = Date::parse(date_str, FMT).or_err(Error::Row, || {
+            format!("{date_str} 无法解析为日期：{cells:?}")
+        })?;
+        let price = regex.replace_all(&cells[dsp], "");
+        v.push(Dsp {
+            code: cells[code].to_lowercase().into(),
+            date: date_parsed,
+            dsp: price
+                .parse()
+                .or_err(Error::Row, || format!("{price} 无法解析为数字：{cells:?}"))?,
+        });
+    }
+    Ok(v)
+}
+
+/// 下载某年的交割结算价，保存到 csv 并录入 qihuo.dce_dsp
+pub fn run_dsp(year: u16) -> Result<()> {
+    let _manifest = util::manifest::guard();
+    let data = fetch_dsp(year)?;
+    if data.is_empty() {
+        info!("{year} 年没有大连交易所的交割结算价数据");
+        util::manifest::finish(None)?;
+        return Ok(());
+    }
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::with_capacity(data.len() * 32));
+    for row in &data {
+        writer
+            .serialize(row)
+            .or_err(Error::Row, || format!("{row:?} 无法写入 csv"))?;
+    }
+    writer.flush()?;
+    let bytes = writer.get_ref();
+    util::save_to_csv_and_clickhouse(
+        || util::save_csv(bytes, format!("dce-dsp-{year}.csv")),
+        || {
+            util::migrate::migrate_once()?;
+            util::clickhouse::insert_with_count_reported(&util::clickhouse::table("dce_dsp"), bytes)
+        },
+    )?;
+    util::manifest::count(data.len() as u64, 0);
+    util::manifest::finish(Some(&util::clickhouse::table("dce_dsp")))?;
+    info!("成功获取大连交易所 {year} 年的 {} 条交割结算价", data.len());
+    Ok(())
+}
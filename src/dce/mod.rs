@@ -1,19 +1,29 @@
-use crate::{util, Result, Str};
+use crate::{error::Context, util, Error, Result, Str};
 use bincode::{Decode, Encode};
 use calamine::{DataType, Reader};
-use color_eyre::eyre::{Context, ContextCompat};
 use indexmap::{Equivalent, IndexMap};
 use serde::{Deserialize, Serialize};
-use std::io;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 use time::Date;
 
 mod parse;
-pub use parse::parse_download_links;
+pub use parse::{parse_download_links, parse_xslx_header};
 mod select;
 pub use select::select;
+mod daily;
+pub use daily::{daily_form, fetch_daily, parse_daily, run_daily, DAILY_URL, PRODUCTS};
+mod dsp;
+pub use dsp::{dsp_form, fetch_dsp, parse_dsp, run_dsp, Dsp, DSP_URL};
 
 pub static DOWNLOAD_LINKS: &[u8] = include_bytes!("../../tests/dce.bincode");
 pub const URL_PREFIX: &str = "http://www.dce.com.cn";
+/// 历史数据页面：包含每年每个品种的下载链接
+pub const HISTORY_URL: &str = "http://www.dce.com.cn/dalianshangpin/xqsj/lssj/index.html";
+/// 刷新后的下载链接保存在缓存目录下的文件名
+pub const LINKS_FILE: &str = "dce-links.bincode";
 
 #[derive(Debug, Decode, Encode, PartialEq, Eq)]
 pub struct DownloadLinks(#[bincode(with_serde)] IndexMap<Key, String>);
@@ -23,9 +33,48 @@ impl DownloadLinks {
         let d = bincode::decode_from_slice::<DownloadLinks, _>(
             DOWNLOAD_LINKS,
             bincode::config::standard(),
-        )?;
+        )
+        .or_err(Error::Links, || "无法反序列化内置的下载链接".into())?;
         Ok(d.0)
     }
+    /// 读取缓存目录下刷新过的下载链接；文件不存在时返回 `Ok(None)`。
+    pub fn new_cached(dir: &Path) -> Result<Option<DownloadLinks>> {
+        let path = dir.join(LINKS_FILE);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let (links, _) =
+            bincode::decode_from_slice::<DownloadLinks, _>(&bytes, bincode::config::standard())
+                .or_err(Error::Links, || format!("无法反序列化 {}", path.display()))?;
+        debug!("使用 {} 中的 {} 条下载链接", path.display(), links.len());
+        Ok(Some(links))
+    }
+    /// 下载历史数据页面，并解析出下载链接。
+    pub fn fetch() -> Result<DownloadLinks> {
+        let _manifest = util::manifest::guard();
+        let html = util::fetch(HISTORY_URL)?;
+        let (html, _) = util::read_txt(html.get_ref(), HISTORY_URL)?;
+        let links = parse_download_links(&html)?;
+        util::manifest::finish(None)?;
+        Ok(links)
+    }
+    /// 保存到缓存目录，之后 `init_data` 会优先使用它。
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(LINKS_FILE);
+        let bytes = bincode::encode_to_vec(self, bincode::config::standard())
+            .or_err(Error::Links, || "无法序列化下载链接".into())?;
+        std::fs::write(&path, bytes)?;
+        info!("{} 条下载链接已写入 {}", self.len(), path.display());
+        Ok(path)
+    }
+    /// 与新的下载链接相比：`(新增的, 移除的)`
+    pub fn diff<'a>(&'a self, new: &'a DownloadLinks) -> (Vec<&'a Key>, Vec<&'a Key>) {
+        let added = new.0.keys().filter(|k| !self.0.contains_key(*k)).collect();
+        let removed = self.0.keys().filter(|k| !new.0.contains_key(*k)).collect();
+        (added, removed)
+    }
     pub fn iter(&self) -> impl Iterator<Item = (&Key, &String)> {
         self.0.iter()
     }
@@ -35,7 +84,7 @@ impl DownloadLinks {
     }
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq, Hash, Serialize, Deserialize)]
 #[cfg_attr(feature = "tabled", derive(tabled::Tabled))]
 pub struct Key {
     pub year: u16,
@@ -55,34 +104,95 @@ impl Equivalent<Key> for (u16, &str) {
     }
 }
 
+/// 有年度数据下载链接的年份：从 2006 年至链接中最新的年份；当年的数据需要按交易日获取（见 [`run_daily`]）
+pub fn link_years() -> std::ops::RangeInclusive<u16> {
+    let first = crate::Exchange::dce.first_year();
+    let last = util::init_data()
+        .links_dce
+        .iter()
+        .map(|(k, _)| k.year)
+        .max();
+    first..=last.unwrap_or(first)
+}
+
+/// 刷新下载链接并保存到缓存目录，返回相比当前所用链接 `(新增的, 移除的)` 的 (年份, 品种)。
+pub fn refresh_links() -> Result<(Vec<Key>, Vec<Key>)> {
+    let init = util::init_data();
+    let new = DownloadLinks::fetch()?;
+    let (added, removed) = init.links_dce.diff(&new);
+    let diff = (
+        added.into_iter().cloned().collect(),
+        removed.into_iter().cloned().collect(),
+    );
+    new.save(&init.cache_dir)?;
+    Ok(diff)
+}
+
 pub fn get_url(year: u16, name: &str) -> Result<String> {
     let index_map = &util::init_data().links_dce.0;
     let postfix = index_map
         .get(&(year, name))
-        .with_context(|| format!("无法找到 {year} 年 {name} 品种的下载链接"))?;
+        .ok_or_else(|| Error::UnknownProduct {
+            year,
+            name: name.into(),
+        })?;
     Ok(format!("{URL_PREFIX}{postfix}"))
 }
 
 /// 读取 xlsx 文件，并处理解析过的每行数据
 pub fn read_xlsx<R: io::Read + io::Seek>(
-    mut wb: calamine::Xlsx<R>,
+    wb: calamine::Xlsx<R>,
     mut handle: impl FnMut(Data) -> Result<()>,
 ) -> Result<()> {
-    let sheet = match wb.worksheet_range_at(0) {
-        Some(Ok(sheet)) => sheet,
-        Some(Err(err)) => bail!("无法读取第 0 个表，因为 {err:?}"),
-        None => bail!("无法读取第 0 个表"),
-    };
-    let mut rows = sheet.rows();
-    let header = rows.next().context("无法读取第一行")?;
-    let pos = parse::parse_xslx_header(header)?;
-    for row in rows {
-        handle(Data::new(row, &pos)?)?;
+    for data in rows(wb) {
+        handle(data?)?;
     }
     Ok(())
 }
 
+/// 逐行解析 xlsx 文件第 0 个表的数据。
+///
+/// 无法读取表或者表头时，迭代器只产生一个错误。
+pub fn rows<R: io::Read + io::Seek>(
+    mut wb: calamine::Xlsx<R>,
+) -> impl Iterator<Item = Result<Data>> {
+    let sheet = match wb.worksheet_range_at(0) {
+        Some(Ok(sheet)) => Ok(sheet),
+        Some(Err(err)) => Err(err!(Archive, "无法读取第 0 个表，因为 {err:?}")),
+        None => Err(err!(Archive, "无法读取第 0 个表")),
+    };
+    util::try_iter(sheet.and_then(|sheet| {
+        let header = sheet
+            .rows()
+            .next()
+            .or_err(Error::Header, || "无法读取第一行".into())?;
+        let pos = parse::parse_xslx_header(header)?;
+        let (height, width) = sheet.get_size();
+        Ok((1..height).map(move |r| {
+            let row: Vec<_> = (0..width)
+                .map(|c| sheet.get((r, c)).cloned().unwrap_or(DataType::Empty))
+                .collect();
+            Data::new(&row, &pos)
+        }))
+    }))
+}
+
+/// 下载并逐行解析某年某品种的数据，不涉及文件和数据库。（暂时只支持 xlsx 链接）
+pub fn fetch_year(year: u16, name: &str) -> impl Iterator<Item = Result<Data>> {
+    let wb = get_url(year, name).and_then(|link| {
+        ensure!(
+            link.ends_with(".xlsx") || link.ends_with(".csv"),
+            Archive,
+            "暂时无法处理 {link}，因为只支持 xlsx 文件"
+        );
+        calamine::Xlsx::new(util::fetch(&link)?)
+            .or_err(Error::Archive, || format!("{link} 无法读取为 xlsx"))
+    });
+    util::try_iter(wb.map(rows))
+}
+
 pub fn run(year: u16, name: &str) -> Result<()> {
+    let _manifest = util::manifest::guard();
     let link = get_url(year, name)?;
     let xlsx = if link.ends_with(".xlsx") || link.ends_with(".csv") {
         // xxx.csv 其实也是 xlsx 文件 :(
@@ -101,6 +211,7 @@ pub fn run(year: u16, name: &str) -> Result<()> {
             // v.push((raw, fname));
             Ok(())
         })?;
+        util::manifest::finish(None)?;
         return Ok(());
         // ensure!(
         //     v.len() == 1,
@@ -109,32 +220,42 @@ pub fn run(year: u16, name: &str) -> Result<()> {
         // );
         // Cursor::new(v.remove(0).0)
     } else {
-        bail!("暂时无法处理 {link}，因为只支持 xlsx 或者 zip 文件");
+        bail!(
+            Archive,
+            "暂时无法处理 {link}，因为只支持 xlsx 或者 zip 文件"
+        );
     };
     let len = xlsx.get_ref().len();
     let mut writer = csv::WriterBuilder::new()
         .has_headers(false)
         .buffer_capacity(len)
         .from_writer(Vec::with_capacity(len));
-    read_xlsx(calamine::Xlsx::new(xlsx)?, |data| {
-        writer.serialize(&data)?;
-        Ok(())
+    let wb =
+        calamine::Xlsx::new(xlsx).or_err(Error::Archive, || format!("{link} 无法读取为 xlsx"))?;
+    read_xlsx(wb, |data| {
+        util::manifest::count(1, 0);
+        writer
+            .serialize(&data)
+            .or_err(Error::Row, || format!("{data:?} 无法写入 csv"))
     })?;
     writer.flush()?;
-    let fname = format!("dce-{year}-{name}.csv");
-    let bytes = writer.get_ref();
+    save(writer.get_ref(), &format!("dce-{year}-{name}.csv"))?;
+    util::manifest::finish(Some(&util::clickhouse::table("dce")))?;
+    Ok(())
+}
+
+/// 保存 csv 文件，并录入到 qihuo.dce
+fn save(bytes: &[u8], fname: &str) -> Result<()> {
     util::save_to_csv_and_clickhouse(
-        || util::save_csv(bytes, &fname),
+        || util::save_csv(bytes, fname),
         || {
-            util::clickhouse::execute(include_str!("../sql/dce.sql"))?;
-            const TABLE: &str = "qihuo.dce";
-            util::clickhouse::insert_with_count_reported(TABLE, writer.get_ref())
+            util::migrate::migrate_once()?;
+            util::clickhouse::insert_with_count_reported(&util::clickhouse::table("dce"), bytes)
         },
-    )?;
-    Ok(())
+    )
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[cfg_attr(feature = "tabled", derive(tabled::Tabled))]
 pub struct Data {
     /// 合约代码
@@ -160,31 +281,45 @@ pub struct Data {
     /// 成交量（双边）
     pub vol: u32,
     /// 交易额（疑似单边、单位元，且貌似约等于 settle*vol/2*品种杠杆）
-    pub amount: u32,
+    pub amount: u64,
     /// 持仓量（双边）
     pub position: u32,
+    /// 前收盘价：部分年份的数据和日行情没有此列
+    #[cfg_attr(
+        feature = "tabled",
+        tabled(display_with = "crate::util::display_option")
+    )]
+    pub prev_close: Option<f32>,
 }
 
 impl Data {
     pub fn new(row: &[DataType], pos: &[usize]) -> Result<Data> {
-        use parse::{as_date, as_f32, as_str, as_u32, LEN};
+        use parse::{as_date, as_f32, as_option_f32, as_str, as_u32, as_u64, LEN};
 
-        ensure!(pos.len() == LEN, "xlsx 的表头有效列不足 {LEN}：{pos:?}");
-        let err = |n: usize| format!("{row:?} 无法获取到第 {n} 个单元格数据");
+        ensure!(
+            pos.len() >= LEN,
+            Header,
+            "xlsx 的表头有效列不足 {LEN}：{pos:?}"
+        );
+        let err = |n: usize| move || format!("{row:?} 无法获取到第 {n} 个单元格数据");
         Ok(Data {
-            code: as_str(row.get(pos[0]).with_context(|| err(0))?)?,
-            date: as_date(row.get(pos[1]).with_context(|| err(1))?)?,
-            prev: as_f32(row.get(pos[2]).with_context(|| err(2))?)?,
-            open: as_f32(row.get(pos[3]).with_context(|| err(3))?)?,
-            high: as_f32(row.get(pos[4]).with_context(|| err(4))?)?,
-            low: as_f32(row.get(pos[5]).with_context(|| err(5))?)?,
-            close: as_f32(row.get(pos[6]).with_context(|| err(6))?)?,
-            settle: as_f32(row.get(pos[7]).with_context(|| err(7))?)?,
-            zd1: as_f32(row.get(pos[8]).with_context(|| err(8))?)?,
-            zd2: as_f32(row.get(pos[9]).with_context(|| err(9))?)?,
-            vol: as_u32(row.get(pos[10]).with_context(|| err(10))?)?,
-            amount: as_u32(row.get(pos[11]).with_context(|| err(11))?)?,
-            position: as_u32(row.get(pos[12]).with_context(|| err(12))?)?,
+            code: as_str(row.get(pos[0]).or_err(Error::Row, err(0))?)?,
+            date: as_date(row.get(pos[1]).or_err(Error::Row, err(1))?)?,
+            prev: as_f32(row.get(pos[2]).or_err(Error::Row, err(2))?)?,
+            open: as_f32(row.get(pos[3]).or_err(Error::Row, err(3))?)?,
+            high: as_f32(row.get(pos[4]).or_err(Error::Row, err(4))?)?,
+            low: as_f32(row.get(pos[5]).or_err(Error::Row, err(5))?)?,
+            close: as_f32(row.get(pos[6]).or_err(Error::Row, err(6))?)?,
+            settle: as_f32(row.get(pos[7]).or_err(Error::Row, err(7))?)?,
+            zd1: as_f32(row.get(pos[8]).or_err(Error::Row, err(8))?)?,
+            zd2: as_f32(row.get(pos[9]).or_err(Error::Row, err(9))?)?,
+            vol: as_u32(row.get(pos[10]).or_err(Error::Row, err(10))?)?,
+            amount: as_u64(row.get(pos[11]).or_err(Error::Row, err(11))?)?,
+            position: as_u32(row.get(pos[12]).or_err(Error::Row, err(12))?)?,
+            prev_close: match pos.get(LEN) {
+                Some(&p) => as_option_f32(row.get(p).or_err(Error::Row, err(LEN))?)?,
+                None => None,
+            },
         })
     }
 }
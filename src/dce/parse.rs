@@ -1,4 +1,4 @@
-use super::{Context, ContextCompat, DataType, Date, DownloadLinks, IndexMap, Key, Result, Str};
+use super::{Context, DataType, Date, DownloadLinks, Error, IndexMap, Key, Result, Str};
 
 pub fn parse_download_links(html: &str) -> Result<DownloadLinks> {
     fn query_err(s: &str) -> String {
@@ -19,56 +19,58 @@ pub fn parse_download_links(html: &str) -> Result<DownloadLinks> {
     const REL: &str = "rel";
     const OPTION: &str = "option";
     const OPT_VALUE: &str = "value";
-    let dom = tl::parse(html, Default::default())?;
+    let dom =
+        tl::parse(html, Default::default()).or_err(Error::Links, || "无法解析 HTML".into())?;
     let parser = dom.parser();
     let uls: Vec<_> = dom
         .query_selector(UL)
-        .with_context(|| query_err(UL))?
+        .or_err(Error::Links, || query_err(UL))?
         .collect();
     let options: Vec<_> = dom
         .query_selector(OPTION)
-        .with_context(|| query_err(OPTION))?
+        .or_err(Error::Links, || query_err(OPTION))?
         .collect();
     let (uls_len, options_len) = (uls.len(), options.len());
     ensure!(
         uls_len == options_len,
+        Links,
         "年份数量 {options_len} 与列表数量 {uls_len} 不相等，需检查 HTML"
     );
     let mut data = IndexMap::with_capacity(options_len * 16);
     for (option, ul) in options.into_iter().zip(uls) {
         let year_str = option
             .get(parser)
-            .with_context(|| get_err(OPTION))?
+            .or_err(Error::Links, || get_err(OPTION))?
             .as_tag()
-            .with_context(|| as_tag_err(OPTION))?
+            .or_err(Error::Links, || as_tag_err(OPTION))?
             .attributes()
             .get(OPT_VALUE)
-            .with_context(|| get_err(OPT_VALUE))?
-            .with_context(|| attribute_err(OPT_VALUE))?
+            .or_err(Error::Links, || get_err(OPT_VALUE))?
+            .or_err(Error::Links, || attribute_err(OPT_VALUE))?
             .as_utf8_str();
         let year = year_str
             .parse::<u16>()
-            .with_context(|| format!("年份 `{year_str}` 无法解析为 u16"))?;
+            .or_err(Error::Links, || format!("年份 `{year_str}` 无法解析为 u16"))?;
         let labels = ul
             .get(parser)
-            .with_context(|| get_err(UL))?
+            .or_err(Error::Links, || get_err(UL))?
             .as_tag()
-            .with_context(|| as_tag_err(UL))?
+            .or_err(Error::Links, || as_tag_err(UL))?
             .query_selector(parser, LABEL)
-            .with_context(|| query_err(LABEL))?;
+            .or_err(Error::Links, || query_err(LABEL))?;
         for label in labels {
-            let label = label.get(parser).with_context(|| get_err(LABEL))?;
+            let label = label.get(parser).or_err(Error::Links, || get_err(LABEL))?;
             let input = label
                 .as_tag()
-                .with_context(|| as_tag_err(LABEL))?
+                .or_err(Error::Links, || as_tag_err(LABEL))?
                 .query_selector(parser, INPUT)
-                .with_context(|| query_err(INPUT))?
+                .or_err(Error::Links, || query_err(INPUT))?
                 .next()
-                .with_context(|| query_err(INPUT))?
+                .or_err(Error::Links, || query_err(INPUT))?
                 .get(parser)
-                .with_context(|| get_err(INPUT))?
+                .or_err(Error::Links, || get_err(INPUT))?
                 .as_tag()
-                .with_context(|| as_tag_err(INPUT))?;
+                .or_err(Error::Links, || as_tag_err(INPUT))?;
             data.insert(
                 Key {
                     year,
@@ -77,8 +79,8 @@ pub fn parse_download_links(html: &str) -> Result<DownloadLinks> {
                 input
                     .attributes()
                     .get(REL)
-                    .with_context(|| get_err(REL))?
-                    .with_context(|| attribute_err(REL))?
+                    .or_err(Error::Links, || get_err(REL))?
+                    .or_err(Error::Links, || attribute_err(REL))?
                     .as_utf8_str()
                     .into_owned(),
             );
@@ -89,14 +91,16 @@ pub fn parse_download_links(html: &str) -> Result<DownloadLinks> {
 }
 
 /// Xlsx 中的数据的正确位置（不同年份具有不同的表头），因此需要先识别表头。
+///
+/// 返回的前 [`LEN`] 个位置对应必需的字段；若存在前收盘价，则它的位置附加在末尾。
 pub fn parse_xslx_header(header: &[DataType]) -> Result<Vec<usize>> {
     use Field::*;
     let mut pos = IndexMap::with_capacity(LEN);
     let mut cols = Vec::with_capacity(LEN + 8);
     for (idx, h) in header.iter().enumerate() {
-        let col = h
-            .get_string()
-            .with_context(|| format!("无法按照字符串读取第一行：{header:?}"))?;
+        let col = h.get_string().or_err(Error::Header, || {
+            format!("无法按照字符串读取第一行：{header:?}")
+        })?;
         cols.push(col);
         match col {
             "合约" => {
@@ -105,9 +109,10 @@ pub fn parse_xslx_header(header: &[DataType]) -> Result<Vec<usize>> {
             "日期" => {
                 pos.insert(日期, idx);
             }
-            // "前收盘价" => {
-            //     pos.insert(前收盘价, idx);
-            // }
+            "前收盘价" | "前收盘" | "昨收盘价" | "昨收盘" => {
+                // 部分年份没有这列，或者名称不同
+                pos.insert(前收盘价, idx);
+            }
             "前结算价" => {
                 pos.insert(前结算价, idx);
             }
@@ -148,7 +153,6 @@ pub fn parse_xslx_header(header: &[DataType]) -> Result<Vec<usize>> {
     const FIELDS: [Field; LEN] = [
         合约,
         日期,
-        // 前收盘价,
         前结算价,
         开盘价,
         最高价,
@@ -161,13 +165,14 @@ pub fn parse_xslx_header(header: &[DataType]) -> Result<Vec<usize>> {
         成交额,
         持仓量,
     ];
-    let len = pos.len();
+    let len = FIELDS.iter().filter(|f| pos.contains_key(*f)).count();
     if len != LEN {
         let missing: Vec<_> = FIELDS
             .into_iter()
             .filter(|f| pos.get(f).is_none())
             .collect();
         bail!(
+            Header,
             "xlsx 的表头有效列只有 {len} 个（不足 {LEN}），\
             缺少 {missing:?}\n有效列应为 {FIELDS:?}\n但实际列为 {cols:?}"
         );
@@ -181,37 +186,47 @@ pub fn parse_xslx_header(header: &[DataType]) -> Result<Vec<usize>> {
 pub fn as_str(cell: &DataType) -> Result<Str> {
     cell.get_string()
         .map(Str::from)
-        .with_context(|| format!("{cell:?} 无法读取为 &str"))
+        .or_err(Error::Row, || format!("{cell:?} 无法读取为 &str"))
 }
 
 pub fn as_date(cell: &DataType) -> Result<Date> {
     use time::{format_description::FormatItem, macros::format_description};
     const FMT: &[FormatItem<'static>] = format_description!("[year][month][day]");
     if let Ok(s) = as_str(cell) {
-        Date::parse(&s, &FMT).map_err(|err| eyre!("{s} 无法解析为日期：{err:?}"))
+        Date::parse(&s, &FMT).or_err(Error::Row, || format!("{s} 无法解析为日期"))
     } else if let Ok(u) = as_u32(cell) {
         let year = u / 10000;
         let minus_year = u - year * 10000;
         let month = (minus_year) / 100;
         let day = minus_year - month * 100;
-        Ok(Date::from_calendar_date(
+        Date::from_calendar_date(
             year.try_into()
-                .with_context(|| format!("{year} 无法转成 i32"))?,
+                .or_err(Error::Row, || format!("{year} 无法转成 i32"))?,
             u8::try_from(month)
-                .with_context(|| format!("{month} 无法转成 u8"))?
+                .or_err(Error::Row, || format!("{month} 无法转成 u8"))?
                 .try_into()
-                .with_context(|| format!("{month} 无法转成月份"))?,
-            u8::try_from(day).with_context(|| format!("{day} 无法转成 u8"))?,
-        )?)
+                .or_err(Error::Row, || format!("{month} 无法转成月份"))?,
+            u8::try_from(day).or_err(Error::Row, || format!("{day} 无法转成 u8"))?,
+        )
+        .or_err(Error::Row, || format!("{u} 无法解析为日期"))
     } else {
-        bail!("{cell} 无法通过 as_str 或 as_u32 解析")
+        bail!(Row, "{cell} 无法通过 as_str 或 as_u32 解析")
     }
 }
 
 pub fn as_f32(cell: &DataType) -> Result<f32> {
     cell.get_float()
         .map(|f| f as f32)
-        .with_context(|| format!("{cell:?} 无法读取为 f32"))
+        .or_err(Error::Row, || format!("{cell:?} 无法读取为 f32"))
+}
+
+/// 空单元格视为没有数据
+pub fn as_option_f32(cell: &DataType) -> Result<Option<f32>> {
+    match cell {
+        DataType::Empty => Ok(None),
+        DataType::String(s) if s.trim().is_empty() || s.trim() == "-" => Ok(None),
+        cell => as_f32(cell).map(Some),
+    }
 }
 
 pub fn as_u32(cell: &DataType) -> Result<u32> {
@@ -219,18 +234,28 @@ pub fn as_u32(cell: &DataType) -> Result<u32> {
         Ok(f as u32)
     } else if let Some(int) = cell.get_int() {
         int.try_into()
-            .map_err(|err| eyre!("{int}i64 无法转化为 u32：{err:?}"))
+            .or_err(Error::Row, || format!("{int}i64 无法转化为 u32"))
+    } else {
+        bail!(Row, "{cell:?} 无法读取为 u32")
+    }
+}
+
+pub fn as_u64(cell: &DataType) -> Result<u64> {
+    if let Some(f) = cell.get_float() {
+        Ok(f as u64)
+    } else if let Some(int) = cell.get_int() {
+        int.try_into()
+            .or_err(Error::Row, || format!("{int}i64 无法转化为 u64"))
     } else {
-        bail!("{cell:?} 无法读取为 u32")
+        bail!(Row, "{cell:?} 无法读取为 u64")
     }
 }
 
-/// 注意：源数据中多了一列“前收盘价”，但尚未研究它；由于 czce 数据不具备它，所以舍弃。
+/// 变体顺序与 Data 的字段顺序一致。前收盘价并非每年都有，因此放在最后且不计入 [`LEN`]。
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum Field {
     合约,
     日期,
-    // 前收盘价,
     前结算价,
     开盘价,
     最高价,
@@ -242,5 +267,7 @@ enum Field {
     成交量,
     成交额,
     持仓量,
+    前收盘价,
 }
+/// 必需的字段数量
 pub const LEN: usize = 13;
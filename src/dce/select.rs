@@ -1,5 +1,5 @@
 use super::{get_url, run, Key, Result};
-use crate::util::init_data;
+use crate::util::{init_data, prefetch, progress};
 use inquire::{InquireError, MultiSelect};
 
 /// `Ok(Some(()))` 表示正常运行；
@@ -25,11 +25,18 @@ pub fn select(with_options: bool) -> Result<Option<()>> {
         Err(InquireError::OperationInterrupted | InquireError::OperationCanceled) => {
             return Ok(None)
         }
-        Err(err) => bail!("交互出现问题：{err:?}"),
+        Err(err) => bail!(Interactive, "{err:?}"),
     };
+    let urls = keys
+        .iter()
+        .map(|k| get_url(k.year, &k.name))
+        .collect::<Result<_>>()?;
+    let _prefetch = prefetch::start(urls);
+    let overall = progress::overall(keys.len(), "dce (年份, 品种)");
     for &Key { year, ref name } in keys {
         info!("正在从 {} 下载文件", get_url(year, name)?);
         run(year, name)?;
+        overall.inc();
     }
     Ok(Some(()))
 }
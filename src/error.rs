@@ -0,0 +1,95 @@
+use crate::Str;
+use std::fmt::Debug;
+
+/// 库函数的错误类型
+///
+/// 各变体的说明信息与日志一致，下游可通过变体区分错误来源，而不必匹配字符串。
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// 网络请求失败：请求本身出错或者读取响应内容出错
+    #[error("网络请求失败：{0}")]
+    Network(String),
+    /// zip 或者 xlsx 文件无法解析
+    #[error("压缩文件解析失败：{0}")]
+    Archive(String),
+    /// 文本编码无法识别
+    #[error("编码错误：{0}")]
+    Encoding(String),
+    /// 表头与预期不一致
+    #[error("表头错误：{0}")]
+    Header(String),
+    /// 数据行无法解析
+    #[error("数据行错误：{0}")]
+    Row(String),
+    /// clickhouse 相关的错误
+    #[error("数据库错误：{0}")]
+    Database(String),
+    /// 无法找到品种的下载链接
+    #[error("无法找到 {year} 年 {name} 品种的下载链接")]
+    UnknownProduct { year: u16, name: Str },
+    /// 年份不在交易所支持的范围内
+    #[error("{year} 必须在 {min}..={max} 范围内")]
+    UnsupportedYear { year: u16, min: u16, max: u16 },
+    /// 下载链接数据无法解析或者无法（反）序列化
+    #[error("下载链接数据错误：{0}")]
+    Links(String),
+    /// 交互选择出现问题
+    #[error("交互出现问题：{0}")]
+    Interactive(String),
+    /// 配置文件或者环境变量无效
+    #[error("配置错误：{0}")]
+    Config(String),
+    /// 结果无法导出（如 parquet 文件）
+    #[error("导出失败：{0}")]
+    Export(String),
+    /// 下载记录无法读写（见 `util::manifest`）
+    #[error("下载记录错误：{0}")]
+    Manifest(String),
+    /// 日志无法开启
+    #[error("日志开启失败：{0}")]
+    Log(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// 变体名，如 `Network`，用于结构化输出（见 `util::event`）
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Network(_) => "Network",
+            Error::Archive(_) => "Archive",
+            Error::Encoding(_) => "Encoding",
+            Error::Header(_) => "Header",
+            Error::Row(_) => "Row",
+            Error::Database(_) => "Database",
+            Error::UnknownProduct { .. } => "UnknownProduct",
+            Error::UnsupportedYear { .. } => "UnsupportedYear",
+            Error::Links(_) => "Links",
+            Error::Interactive(_) => "Interactive",
+            Error::Config(_) => "Config",
+            Error::Export(_) => "Export",
+            Error::Manifest(_) => "Manifest",
+            Error::Log(_) => "Log",
+            Error::Io(_) => "Io",
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// 为 `Option` 和 `Result` 附加说明信息，并归入指定的错误类型。
+pub(crate) trait Context<T> {
+    fn or_err(self, kind: fn(String) -> Error, msg: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> Context<T> for Option<T> {
+    fn or_err(self, kind: fn(String) -> Error, msg: impl FnOnce() -> String) -> Result<T> {
+        self.ok_or_else(|| kind(msg()))
+    }
+}
+
+impl<T, E: Debug> Context<T> for Result<T, E> {
+    fn or_err(self, kind: fn(String) -> Error, msg: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|err| kind(format!("{}：{err:?}", msg())))
+    }
+}
@@ -5,50 +5,81 @@ extern crate log;
 
 #[macro_use]
 mod macros {
-    #[macro_export]
+    /// `bail!(Kind, "...")` 即 `return Err(Error::Kind(format!("...")))`
     macro_rules! bail {
-        ($($t:tt)*) => { ::color_eyre::eyre::bail!($($t)*) };
+        ($kind:ident, $($t:tt)*) => { return Err($crate::Error::$kind(format!($($t)*))) };
     }
-    #[macro_export]
-    macro_rules! eyre {
-        ($($t:tt)*) => { ::color_eyre::eyre::eyre!($($t)*) };
+    /// `err!(Kind, "...")` 即 `Error::Kind(format!("..."))`
+    macro_rules! err {
+        ($kind:ident, $($t:tt)*) => { $crate::Error::$kind(format!($($t)*)) };
     }
-    #[macro_export]
     macro_rules! ensure {
-        ($($t:tt)*) => { ::color_eyre::eyre::ensure!($($t)*) };
+        ($cond:expr, $kind:ident, $($t:tt)*) => {
+            if !$cond {
+                bail!($kind, $($t)*);
+            }
+        };
     }
 }
 
+/// 基于统一日线数据的分析
+pub mod analytics;
 /// 合并各个交易所数据到一个表
 pub mod ce;
 /// 郑州商品交易所
 pub mod czce;
 /// 大连商品交易所
 pub mod dce;
+/// 错误类型
+pub mod error;
+/// 试运行的计划
+pub mod plan;
+/// 数据覆盖情况
+pub mod status;
 
 /// 辅助
 pub mod util;
 
-pub use color_eyre::eyre::Result;
+pub use error::{Error, Result};
+use serde::{Deserialize, Serialize};
 pub type Str = compact_str::CompactString;
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Exchange {
     czce,
     dce,
 }
 
 impl Exchange {
+    /// 交易所提供数据的最早年份
+    pub fn first_year(self) -> u16 {
+        match self {
+            Exchange::czce => 2010,
+            Exchange::dce => 2006,
+        }
+    }
+
+    /// 支持的年份范围：最早年份至今年
+    pub fn years(self) -> std::ops::RangeInclusive<u16> {
+        self.first_year()..=util::init_data().this_year
+    }
+
     pub fn run(self, year: u16) -> Result<()> {
         match self {
-            Exchange::czce => czce::run(year)?,
+            Exchange::czce => czce::run(year, &czce::Filter::default())?,
             Exchange::dce => eprintln!("{:?}", util::init_data().links_dce),
         }
         Ok(())
     }
 }
 
+impl std::fmt::Display for Exchange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
 impl std::str::FromStr for Exchange {
     type Err = String;
 
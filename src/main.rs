@@ -5,12 +5,19 @@ extern crate log;
 // extern crate commodity_exchange_zh;
 
 mod cli;
-use commodity_exchange_zh::{util::init_log, Result, Str};
+use color_eyre::eyre::Result;
+use commodity_exchange_zh::{
+    util::{event::Output, init_json_log, init_log},
+    Str,
+};
 
 fn main() -> Result<()> {
     color_eyre::install()?;
-    init_log()?;
     let args: cli::Args = argh::from_env();
+    match args.output() {
+        Output::Text => init_log()?,
+        Output::Json => init_json_log()?,
+    }
     args.run()?;
     Ok(())
 }
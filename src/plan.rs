@@ -0,0 +1,252 @@
+//! 试运行（`--dry-run`）：不联网、不访问数据库，列出将要下载的链接、保存的文件、录入的表以及执行的 SQL。
+//!
+//! 链接与实际运行时相同（[`czce::get_url`]、[`dce::get_url`] 等），SQL 按配置中的数据库名和表名前缀渲染。
+use crate::{
+    ce, czce, dce,
+    util::{self, clickhouse, manifest, migrate, Sink},
+    Result,
+};
+use serde::Serialize;
+use time::Date;
+
+/// 一次下载
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Download {
+    /// GET 或者 POST（表单）
+    pub method: &'static str,
+    pub url: String,
+    /// POST 的表单
+    pub form: Vec<(String, String)>,
+    /// 保存到缓存目录的 csv 文件名；未启用 csv 或者不保存时为 None
+    pub csv: Option<String>,
+    /// 录入的表；未启用 clickhouse 或者不录入时为 None
+    pub table: Option<String>,
+}
+
+/// 一条（或者一组）SQL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Statement {
+    /// 执行的时机
+    pub when: String,
+    pub sql: String,
+}
+
+/// 试运行的计划
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    pub downloads: Vec<Download>,
+    /// 录入的表，按首次录入的顺序
+    pub tables: Vec<String>,
+    /// 按执行顺序排列的 SQL
+    pub sql: Vec<Statement>,
+}
+
+/// 逐个添加下载，最后由 [`Planner::finish`] 生成计划
+#[derive(Debug, Default)]
+pub struct Planner {
+    downloads: Vec<Download>,
+    tables: Vec<String>,
+    /// 郑州交易所 GBK 编码的源文件录入后修正 dsp
+    fixups: Vec<Statement>,
+}
+
+impl Planner {
+    fn add(
+        &mut self,
+        method: &'static str,
+        url: String,
+        form: &[(&str, String)],
+        csv: String,
+        table: &str,
+    ) {
+        let config = util::config();
+        let table = config
+            .sink_enabled(Sink::Clickhouse)
+            .then(|| clickhouse::table(table));
+        if let Some(table) = &table {
+            if !self.tables.contains(table) {
+                self.tables.push(table.clone());
+            }
+        }
+        self.downloads.push(Download {
+            method,
+            url,
+            form: form
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), v.clone()))
+                .collect(),
+            csv: config.sink_enabled(Sink::Csv).then_some(csv),
+            table,
+        });
+    }
+
+    fn fixup(&mut self, url: &str, scope: &str) {
+        if util::config().sink_enabled(Sink::Clickhouse) {
+            self.fixups.push(Statement {
+                when: format!("{url} 为 GBK 编码时，录入后"),
+                sql: czce::dsp_fixup(&clickhouse::table("czce"), scope),
+            });
+        }
+    }
+
+    /// 郑州交易所某年的数据（见 [`czce::run`]）；不在筛选的日期范围内时跳过
+    pub fn czce(&mut self, year: u16, filter: &czce::Filter) -> Result<()> {
+        if !filter.contains_year(year) {
+            return Ok(());
+        }
+        let url = czce::get_url(year)?;
+        self.add(
+            "GET",
+            url.clone(),
+            &[],
+            "czce-<zip 内的文件名>.csv".into(),
+            "czce",
+        );
+        self.fixup(&url, &czce::year_scope(year));
+        Ok(())
+    }
+
+    /// 郑州交易所某交易日的日行情（见 [`czce::run_daily`]）
+    pub fn czce_daily(&mut self, date: Date) {
+        let url = czce::get_daily_url(date);
+        self.add(
+            "GET",
+            url.clone(),
+            &[],
+            format!("czce-daily-{date}.csv"),
+            "czce",
+        );
+        self.fixup(&url, &czce::daily_scope(date));
+    }
+
+    /// 大连交易所某年某品种的数据（见 [`dce::run`]）；zip 文件只下载，不保存也不录入
+    pub fn dce(&mut self, year: u16, name: &str) -> Result<()> {
+        let url = dce::get_url(year, name)?;
+        if url.ends_with(".zip") {
+            self.downloads.push(Download {
+                method: "GET",
+                url,
+                form: Vec::new(),
+                csv: None,
+                table: None,
+            });
+        } else if url.ends_with(".xlsx") || url.ends_with(".csv") {
+            self.add("GET", url, &[], format!("dce-{year}-{name}.csv"), "dce");
+        } else {
+            bail!(Archive, "暂时无法处理 {url}，因为只支持 xlsx 或者 zip 文件");
+        }
+        Ok(())
+    }
+
+    /// 大连交易所某交易日的日行情（见 [`dce::run_daily`]）
+    pub fn dce_daily(&mut self, date: Date) {
+        let form = dce::daily_form(date);
+        self.add(
+            "POST",
+            dce::DAILY_URL.into(),
+            &form,
+            format!("dce-daily-{date}.csv"),
+            "dce",
+        );
+    }
+
+    /// 大连交易所某年的交割结算价（见 [`dce::run_dsp`]）
+    pub fn dce_dsp(&mut self, year: u16) {
+        let form = dce::dsp_form(year);
+        self.add(
+            "POST",
+            dce::DSP_URL.into(),
+            &form,
+            format!("dce-dsp-{year}.csv"),
+            "dce_dsp",
+        );
+    }
+
+    /// 生成计划：迁移、录入和去重、dsp 修正、下载记录，最后重新生成 `qihuo.ce`
+    pub fn finish(self) -> Plan {
+        let mut sql = Vec::new();
+        if util::config().sink_enabled(Sink::Clickhouse) && !self.tables.is_empty() {
+            sql.extend(migrations());
+            for table in &self.tables {
+                let [insert, dedup] = clickhouse::insert_statements(table);
+                sql.push(Statement {
+                    when: format!("每个文件录入 {table} 时"),
+                    sql: format!("{insert};\n{dedup};"),
+                });
+            }
+            sql.extend(self.fixups);
+            sql.push(Statement {
+                when: "每个文件录入后，记录下载".into(),
+                sql: manifest::insert_sql(),
+            });
+            sql.push(Statement {
+                when: format!("录入完成后，重新生成 {}", clickhouse::table("ce")),
+                sql: clickhouse::render(ce::SQL),
+            });
+        }
+        Plan {
+            downloads: self.downloads,
+            tables: self.tables,
+            sql,
+        }
+    }
+}
+
+/// 迁移的 SQL：已执行的迁移会被跳过，而试运行不查询数据库，所以列出全部
+pub fn migrations() -> Vec<Statement> {
+    let mut v = vec![Statement {
+        when: "录入之前，创建迁移记录表".into(),
+        sql: migrate::bookkeeping_sql(),
+    }];
+    v.extend(migrate::MIGRATIONS.iter().map(|m| Statement {
+        when: format!("迁移 {:04}_{} 尚未执行时", m.version, m.name),
+        sql: format!(
+            "{}\n{};",
+            clickhouse::render(m.sql).trim_end(),
+            migrate::record_sql(m)
+        ),
+    }));
+    v
+}
+
+/// 大连交易所下载链接页面（见 [`dce::refresh_links`]）：只下载，不录入
+pub fn links_refresh() -> Plan {
+    Plan {
+        downloads: vec![Download {
+            method: "GET",
+            url: dce::HISTORY_URL.into(),
+            form: Vec::new(),
+            csv: None,
+            table: None,
+        }],
+        ..Plan::default()
+    }
+}
+
+impl Plan {
+    /// 以文本显示
+    pub fn to_text(&self) -> String {
+        let mut s = format!("下载 {} 个文件：\n", self.downloads.len());
+        for d in &self.downloads {
+            s.push_str(&format!("{} {}", d.method, d.url));
+            if !d.form.is_empty() {
+                let form: Vec<_> = d.form.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                s.push_str(&format!(" [{}]", form.join("&")));
+            }
+            if let Some(csv) = &d.csv {
+                s.push_str(&format!(" -> {csv}"));
+            }
+            if let Some(table) = &d.table {
+                s.push_str(&format!(" -> {table}"));
+            }
+            s.push('\n');
+        }
+        if !self.tables.is_empty() {
+            s.push_str(&format!("\n录入的表：{}\n", self.tables.join(", ")));
+        }
+        for st in &self.sql {
+            s.push_str(&format!("\n-- {}\n{}\n", st.when, st.sql.trim_end()));
+        }
+        s
+    }
+}
@@ -1,6 +1,7 @@
 /* 适用于 czce/dce */
-DROP TABLE IF EXISTS qihuo.ce;
-CREATE TABLE IF NOT EXISTS qihuo.ce (
+CREATE DATABASE IF NOT EXISTS {database};
+DROP TABLE IF EXISTS {database}.{prefix}ce;
+CREATE TABLE IF NOT EXISTS {database}.{prefix}ce (
   date     Date    COMMENT '日期',
   code     String  COMMENT '合约代码',
   open     Float32 COMMENT '开盘价',
@@ -11,26 +12,33 @@ CREATE TABLE IF NOT EXISTS qihuo.ce (
   vol      UInt32  COMMENT '成交量（单边）',
   amount   Float32 COMMENT '交易额（万元）',
   position UInt32  COMMENT '持仓量（单边）',
-  ce       Enum('czce' = 1, 'dce' = 2) COMMENT '交易所'
+  ce       Enum('czce' = 1, 'dce' = 2) COMMENT '交易所',
+  prev_close Nullable(Float32) COMMENT '前收盘价（czce 为 NULL）',
+  dsp      Nullable(Float32) COMMENT '交割结算价（只有交割日有数据）'
 ) ENGINE = ReplacingMergeTree
 PRIMARY KEY (ce, date, code)
 ORDER BY    (ce, date, code);
 
 /* czce: 2020 及其之后的数据数据 */
-INSERT INTO qihuo.ce
-SELECT date, upper(code), open, high, low, close, settle, vol/2, amount/2, position/2, 'czce'
-FROM qihuo.czce
+INSERT INTO {database}.{prefix}ce
+SELECT date, upper(code), open, high, low, close, settle, vol/2, amount/2, position/2, 'czce', NULL, dsp
+FROM {database}.{prefix}czce
 WHERE date < '2020-01-01';
 
 /* czce: 2020 及其之后的数据数据 */
-INSERT INTO qihuo.ce
-SELECT date, upper(code), open, high, low, close, settle, vol, amount, position, 'czce'
-FROM qihuo.czce
+INSERT INTO {database}.{prefix}ce
+SELECT date, upper(code), open, high, low, close, settle, vol, amount, position, 'czce', NULL, dsp
+FROM {database}.{prefix}czce
 WHERE date >= '2020-01-01';
 
-/* dce */
-INSERT INTO qihuo.ce
-SELECT date, upper(code), open, high, low, close, settle, vol/2, amount/10000, position/2, 'dce'
-FROM qihuo.dce;
+/* dce: 与 czce 一致，交割结算价放在合约最后一个交易日 */
+INSERT INTO {database}.{prefix}ce
+SELECT date, upper(code), open, high, low, close, settle, vol/2, amount/10000, position/2, 'dce', prev_close, d.dsp
+FROM {database}.{prefix}dce
+LEFT JOIN (
+  SELECT code, last.date AS date, toNullable(p.dsp) AS dsp
+  FROM (SELECT code, max(date) AS date FROM {database}.{prefix}dce GROUP BY code) AS last
+  INNER JOIN {database}.{prefix}dce_dsp AS p USING (code)
+) AS d USING (code, date);
 
-SELECT COUNT() FROM qihuo.ce;
+SELECT COUNT() FROM {database}.{prefix}ce;
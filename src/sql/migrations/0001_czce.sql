@@ -1,7 +1,7 @@
 /* for clickhouse
-DROP TABLE IF EXISTS qihuo.czce;
+DROP TABLE IF EXISTS {database}.{prefix}czce;
 */
-CREATE TABLE IF NOT EXISTS qihuo.czce (
+CREATE TABLE IF NOT EXISTS {database}.{prefix}czce (
   date      Date              COMMENT '日期',
   code      String            COMMENT '合约代码',
   prev      Float32           COMMENT '昨结算',
@@ -22,6 +22,6 @@ PRIMARY KEY (date, code)
 ORDER BY    (date, code);
 /*
 SET format_csv_delimiter = '|';
-INSERT INTO qihuo.czce FROM INFILE 'cache/郑州-ALLFUTURES2022.csv';
-SELECT count(*) FROM qihuo.czce;
+INSERT INTO {database}.{prefix}czce FROM INFILE 'cache/郑州-ALLFUTURES2022.csv';
+SELECT count(*) FROM {database}.{prefix}czce;
 */
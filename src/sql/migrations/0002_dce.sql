@@ -1,7 +1,7 @@
 /*
-DROP TABLE IF EXISTS qihuo.dce;
+DROP TABLE IF EXISTS {database}.{prefix}dce;
 */
-CREATE TABLE IF NOT EXISTS qihuo.dce (
+CREATE TABLE IF NOT EXISTS {database}.{prefix}dce (
   code      String            COMMENT '合约代码',
   date      Date              COMMENT '日期',
   prev      Float32           COMMENT '昨结算',
@@ -19,6 +19,6 @@ CREATE TABLE IF NOT EXISTS qihuo.dce (
 PRIMARY KEY (date, code)
 ORDER BY    (date, code);
 /*
-INSERT INTO qihuo.dce FROM INFILE 'cache/dce-2017-聚氯乙烯.csv';
-SELECT count(*) FROM qihuo.dce;
+INSERT INTO {database}.{prefix}dce FROM INFILE 'cache/dce-2017-聚氯乙烯.csv';
+SELECT count(*) FROM {database}.{prefix}dce;
 */
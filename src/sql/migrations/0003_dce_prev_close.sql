@@ -0,0 +1,3 @@
+/* dce 保留前收盘价：追加在最后一列，与 csv 的列顺序一致；没有该列的数据为 NULL */
+ALTER TABLE {database}.{prefix}dce
+  ADD COLUMN IF NOT EXISTS prev_close Nullable(Float32) COMMENT '前收盘价';
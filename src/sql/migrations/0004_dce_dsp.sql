@@ -0,0 +1,8 @@
+/* dce 的交割结算价单独发布，按 (code, date) 与 dce 表关联 */
+CREATE TABLE IF NOT EXISTS {database}.{prefix}dce_dsp (
+  code      String            COMMENT '合约代码',
+  date      Date              COMMENT '交割日期',
+  dsp       Float32           COMMENT '交割结算价'
+) ENGINE = ReplacingMergeTree
+PRIMARY KEY (date, code)
+ORDER BY    (date, code);
@@ -0,0 +1,18 @@
+/* 主力合约序列：由 `ce build main` 生成 */
+CREATE TABLE IF NOT EXISTS {database}.{prefix}ce_main (
+  date     Date    COMMENT '日期',
+  product  String  COMMENT '品种代码',
+  code     String  COMMENT '主力合约代码',
+  open     Float32 COMMENT '开盘价',
+  high     Float32 COMMENT '最高价',
+  low      Float32 COMMENT '最低价',
+  close    Float32 COMMENT '收盘价',
+  settle   Float32 COMMENT '结算价',
+  vol      UInt32  COMMENT '成交量（单边）',
+  amount   Float32 COMMENT '交易额（万元）',
+  position UInt32  COMMENT '持仓量（单边）',
+  ce       Enum('czce' = 1, 'dce' = 2) COMMENT '交易所',
+  roll     Bool    COMMENT '是否为换月后的第一天'
+) ENGINE = ReplacingMergeTree
+PRIMARY KEY (product, date)
+ORDER BY    (product, date);
@@ -0,0 +1,16 @@
+/* 复权连续合约：由 `ce build continuous` 生成，不同复权方式的数据可以共存 */
+CREATE TABLE IF NOT EXISTS {database}.{prefix}ce_continuous (
+  date      Date    COMMENT '日期',
+  product   String  COMMENT '品种代码',
+  code      String  COMMENT '主力合约代码',
+  open      Float32 COMMENT '复权开盘价',
+  high      Float32 COMMENT '复权最高价',
+  low       Float32 COMMENT '复权最低价',
+  close     Float32 COMMENT '复权收盘价',
+  settle    Float32 COMMENT '复权结算价',
+  factor    Float64 COMMENT '复权因子：diff 为加上的价差，ratio 为乘以的比例',
+  method    Enum('diff' = 1, 'ratio' = 2)         COMMENT '复权方式',
+  direction Enum('backward' = 1, 'forward' = 2)   COMMENT '复权方向'
+) ENGINE = ReplacingMergeTree
+PRIMARY KEY (product, method, direction, date)
+ORDER BY    (product, method, direction, date);
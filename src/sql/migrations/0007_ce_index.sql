@@ -0,0 +1,18 @@
+/* 品种指数：由 `ce build index` 生成，不同加权方式的数据可以共存 */
+CREATE TABLE IF NOT EXISTS {database}.{prefix}ce_index (
+  date      Date    COMMENT '日期',
+  product   String  COMMENT '品种代码',
+  open      Float32 COMMENT '加权开盘价',
+  high      Float32 COMMENT '加权最高价',
+  low       Float32 COMMENT '加权最低价',
+  close     Float32 COMMENT '加权收盘价',
+  settle    Float32 COMMENT '加权结算价',
+  vol       UInt64  COMMENT '成交量之和（单边）',
+  amount    Float64 COMMENT '交易额之和（万元）',
+  position  UInt64  COMMENT '持仓量之和（单边）',
+  contracts UInt32  COMMENT '参与加权的合约数量',
+  weight    Enum('vol' = 1, 'oi' = 2, 'prev_oi' = 3) COMMENT '加权方式',
+  ce        Enum('czce' = 1, 'dce' = 2) COMMENT '交易所'
+) ENGINE = ReplacingMergeTree
+PRIMARY KEY (product, weight, date)
+ORDER BY    (product, weight, date);
@@ -0,0 +1,13 @@
+/* 下载记录：与缓存目录下的 manifest.jsonl 相同，见 `util::manifest` */
+CREATE TABLE IF NOT EXISTS {database}.{prefix}_ce_ingest (
+  url        String                        COMMENT '下载链接',
+  fetched_at DateTime('Asia/Shanghai')     COMMENT '下载完成的时间',
+  bytes      UInt64                        COMMENT '字节数',
+  sha256     FixedString(64)               COMMENT '内容的 SHA-256',
+  encoding   Nullable(Enum('UTF8' = 1, 'GBK' = 2)) COMMENT '文本编码',
+  files      Array(String)                 COMMENT 'zip 文件中的文件名',
+  parsed     UInt64                        COMMENT '解析成功的行数',
+  rejected   UInt64                        COMMENT '无法解析而跳过的行数',
+  table      Nullable(String)              COMMENT '录入的表'
+) ENGINE = MergeTree
+ORDER BY fetched_at;
@@ -0,0 +1,5 @@
+/* 下载记录：补充录入的行数、去重去除的行数和耗时，见 `util::manifest::Entry` */
+ALTER TABLE {database}.{prefix}_ce_ingest
+  ADD COLUMN IF NOT EXISTS inserted     Nullable(UInt64) COMMENT '录入 clickhouse 的行数',
+  ADD COLUMN IF NOT EXISTS deduplicated Nullable(UInt64) COMMENT '去重时去除的行数',
+  ADD COLUMN IF NOT EXISTS elapsed_ms   UInt64           COMMENT '从开始下载到录入完成的毫秒数';
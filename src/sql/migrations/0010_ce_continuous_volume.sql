@@ -0,0 +1,5 @@
+/* 复权连续合约：补充主力合约的成交量、交易额和持仓量（不复权），列的顺序与 `ContinuousBar` 一致 */
+ALTER TABLE {database}.{prefix}ce_continuous
+  ADD COLUMN IF NOT EXISTS vol      UInt32  COMMENT '成交量（单边）' AFTER settle,
+  ADD COLUMN IF NOT EXISTS amount   Float32 COMMENT '交易额（万元）' AFTER vol,
+  ADD COLUMN IF NOT EXISTS position UInt32  COMMENT '持仓量（单边）' AFTER amount;
@@ -0,0 +1,3 @@
+/* 下载记录：解析或者录入出错的记录，见 `util::manifest::guard` */
+ALTER TABLE {database}.{prefix}_ce_ingest
+  ADD COLUMN IF NOT EXISTS error Nullable(String) COMMENT '下载之后解析或者录入出错时的说明';
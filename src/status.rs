@@ -0,0 +1,278 @@
+//! 数据覆盖情况：结合缓存目录下的文件、大连交易所的下载链接以及各表的统计，
+//! 得到每个 (交易所, 年份, 品种) 的条数、起止日期和下载时间。
+use crate::{
+    ce::{self, Bar},
+    dce,
+    error::Context,
+    util::{self, clickhouse, Sink},
+    Error, Exchange, Result, Str,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use time::{Date, OffsetDateTime};
+
+/// 某个 (交易所, 年份, 品种) 的覆盖情况
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Coverage {
+    pub ce: Exchange,
+    pub year: u16,
+    /// 品种代码（大写）；大连交易所无法对应到代码的品种为中文名，没有任何数据的年份为空
+    pub product: Str,
+    /// 大连交易所的品种中文名
+    pub name: Option<Str>,
+    /// 数据条数
+    pub rows: u64,
+    pub first: Option<Date>,
+    pub last: Option<Date>,
+    /// 缓存文件最近一次写入的时间（UTC+8）
+    pub fetched: Option<OffsetDateTime>,
+    /// 是否在大连交易所的下载链接中
+    pub listed: bool,
+}
+
+impl Coverage {
+    /// 在下载链接中，却从未录入：没有数据；期权等无法对应到代码的品种则以没有同名缓存文件为准
+    pub fn missing(&self) -> bool {
+        self.listed && self.rows == 0 && (self.has_code() || self.fetched.is_none())
+    }
+
+    /// 品种能否对应到期货代码
+    fn has_code(&self) -> bool {
+        self.name.as_deref() != Some(self.product.as_str())
+    }
+}
+
+/// 一个 (交易所, 年份, 品种) 的统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stat {
+    pub rows: u64,
+    pub first: Date,
+    pub last: Date,
+}
+
+type Stats = BTreeMap<(Exchange, u16, Str), Stat>;
+
+/// 按 (交易所, 年份, 品种) 统计已读取的数据
+pub fn stats_from_bars(bars: &[Bar]) -> Stats {
+    let mut stats = Stats::new();
+    for bar in bars {
+        let product = util::product(&bar.code).to_uppercase().into();
+        let year = bar.date.year() as u16;
+        stats
+            .entry((bar.ce, year, product))
+            .and_modify(|s: &mut Stat| {
+                s.rows += 1;
+                s.first = s.first.min(bar.date);
+                s.last = s.last.max(bar.date);
+            })
+            .or_insert(Stat {
+                rows: 1,
+                first: bar.date,
+                last: bar.date,
+            });
+    }
+    stats
+}
+
+/// 解析 `SELECT toYear(date), product, min(date), max(date), count() ... FORMAT TSV` 的结果
+pub fn parse_stats(ce: Exchange, tsv: &str) -> Result<Stats> {
+    let err = |line: &str| {
+        let line = line.to_owned();
+        move || format!("{line:?} 无法解析为统计数据")
+    };
+    let date =
+        |s: &str| Date::parse(s, time::macros::format_description!("[year]-[month]-[day]")).ok();
+    let mut stats = Stats::new();
+    for line in tsv.lines().filter(|l| !l.trim().is_empty()) {
+        let cells: Vec<_> = line.split('\t').collect();
+        let [year, product, first, last, rows] = cells[..] else {
+            bail!(Database, "{line:?} 不是 5 列");
+        };
+        let stat = Stat {
+            rows: rows.parse::<u64>().or_err(Error::Database, err(line))?,
+            first: date(first).or_err(Error::Database, err(line))?,
+            last: date(last).or_err(Error::Database, err(line))?,
+        };
+        let year = year.parse::<u16>().or_err(Error::Database, err(line))?;
+        stats.insert((ce, year, product.to_uppercase().into()), stat);
+    }
+    Ok(stats)
+}
+
+/// 从 `qihuo.czce` 和 `qihuo.dce` 统计；表不存在时视为没有数据
+pub fn stats_from_tables(exchanges: &[Exchange]) -> Result<Stats> {
+    let mut stats = Stats::new();
+    for &ce in exchanges {
+        let table = clickhouse::table(&ce.to_string());
+        if clickhouse::query(&format!("EXISTS TABLE {table}"))?.trim() != "1" {
+            continue;
+        }
+        let tsv = clickhouse::query(&format!(
+            "SELECT toYear(date), extract(code, '^[A-Za-z]+') AS product, \
+             min(date), max(date), count() FROM {table} GROUP BY 1, 2 FORMAT TSV"
+        ))?;
+        stats.extend(parse_stats(ce, &tsv)?);
+    }
+    Ok(stats)
+}
+
+/// 缓存目录下的一个数据文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheFile {
+    pub ce: Exchange,
+    pub year: u16,
+    /// 大连交易所年数据的品种中文名；其他文件包含所有品种
+    pub name: Option<Str>,
+    pub modified: OffsetDateTime,
+}
+
+impl CacheFile {
+    /// 根据文件名识别：`czce-*{年份}*.csv`、`czce-daily-{日期}.csv`、
+    /// `dce-{年份}-{品种}.csv`、`dce-daily-{日期}.csv`；其他文件返回 None
+    pub fn parse(fname: &str, modified: OffsetDateTime) -> Option<CacheFile> {
+        let stem = fname.strip_suffix(".csv")?;
+        let (ce, rest) = if let Some(rest) = stem.strip_prefix("czce-") {
+            (Exchange::czce, rest)
+        } else if let Some(rest) = stem.strip_prefix("dce-") {
+            (Exchange::dce, rest)
+        } else {
+            return None;
+        };
+        if rest.starts_with("dsp-") {
+            return None;
+        }
+        let name = match (ce, rest.split_once('-')) {
+            (Exchange::dce, Some((year, name))) if year.len() == 4 => Some(name.into()),
+            _ => None,
+        };
+        // 第一个连续的 4 位数字为年份
+        let year = rest
+            .as_bytes()
+            .windows(4)
+            .find(|w| w.iter().all(u8::is_ascii_digit))
+            .and_then(|w| std::str::from_utf8(w).ok()?.parse().ok())?;
+        Some(CacheFile {
+            ce,
+            year,
+            name,
+            modified,
+        })
+    }
+
+    /// 读取缓存目录下的数据文件
+    pub fn list() -> Result<Vec<CacheFile>> {
+        let dir = &util::init_data().cache_dir;
+        let mut v = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let Ok(fname) = entry.file_name().into_string() else {
+                continue;
+            };
+            let modified = OffsetDateTime::from(entry.metadata()?.modified()?)
+                .to_offset(time::macros::offset!(+8));
+            v.extend(CacheFile::parse(&fname, modified));
+        }
+        Ok(v)
+    }
+}
+
+/// 下载链接中的品种名与日行情中的不同
+const DCE_ALIASES: &[(&str, &str)] = &[
+    ("黄大豆1号", "豆一"),
+    ("黄大豆2号", "豆二"),
+    ("线型低密度聚乙烯", "聚乙烯"),
+];
+
+/// 大连交易所品种中文名对应的代码（大写）；期权没有对应的代码
+fn dce_code(name: &str) -> Option<Str> {
+    let name = DCE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, name)| name);
+    dce::PRODUCTS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, code)| code.to_uppercase().into())
+}
+
+/// 合并统计、缓存文件和下载链接，按 (交易所, 年份, 品种) 排序：
+/// * 大连交易所下载链接中的每个 (年份, 品种) 都会出现，从未录入的 [`Coverage::missing`] 为 true
+/// * 郑州交易所没有任何数据的年份以空品种出现
+pub fn coverage(
+    exchanges: &[Exchange],
+    stats: &Stats,
+    files: &[CacheFile],
+    links: &dce::DownloadLinks,
+) -> Vec<Coverage> {
+    type Map = BTreeMap<(Exchange, u16, Str), Coverage>;
+    fn entry(map: &mut Map, ce: Exchange, year: u16, product: Str) -> &mut Coverage {
+        map.entry((ce, year, product.clone()))
+            .or_insert_with(|| Coverage {
+                ce,
+                year,
+                product,
+                name: None,
+                rows: 0,
+                first: None,
+                last: None,
+                fetched: None,
+                listed: false,
+            })
+    }
+    let mut map = Map::new();
+    for ((ce, year, product), stat) in stats {
+        if !exchanges.contains(ce) {
+            continue;
+        }
+        let c = entry(&mut map, *ce, *year, product.clone());
+        c.rows = stat.rows;
+        c.first = Some(stat.first);
+        c.last = Some(stat.last);
+    }
+    if exchanges.contains(&Exchange::dce) {
+        for (key, _) in links.iter() {
+            let product = dce_code(&key.name).unwrap_or_else(|| key.name.clone());
+            let c = entry(&mut map, Exchange::dce, key.year, product);
+            c.name = Some(key.name.clone());
+            c.listed = true;
+        }
+    }
+    if exchanges.contains(&Exchange::czce) {
+        let years: Vec<_> = map
+            .keys()
+            .filter(|(ce, ..)| *ce == Exchange::czce)
+            .map(|(_, year, _)| *year)
+            .collect();
+        for year in Exchange::czce.years().filter(|y| !years.contains(y)) {
+            entry(&mut map, Exchange::czce, year, Str::default());
+        }
+    }
+    for c in map.values_mut() {
+        let (name, has_code) = (c.name.as_deref(), c.has_code());
+        // 日行情等包含所有期货品种的文件不包含期权
+        c.fetched = files
+            .iter()
+            .filter(|f| f.ce == c.ce && f.year == c.year)
+            .filter(|f| (has_code && f.name.is_none()) || f.name.as_deref() == name)
+            .map(|f| f.modified)
+            .max();
+    }
+    map.into_values().collect()
+}
+
+/// 按照配置统计覆盖情况：启用 clickhouse 时统计各交易所的表，否则统计缓存的 csv 文件
+pub fn run() -> Result<Vec<Coverage>> {
+    let config = util::config();
+    let stats = if config.sink_enabled(Sink::Clickhouse) {
+        stats_from_tables(&config.exchanges)?
+    } else {
+        stats_from_bars(&ce::load_cache(&Default::default())?)
+    };
+    let files = CacheFile::list()?;
+    Ok(coverage(
+        &config.exchanges,
+        &stats,
+        &files,
+        &util::init_data().links_dce,
+    ))
+}
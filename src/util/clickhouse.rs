@@ -1,12 +1,20 @@
-use super::{io, ByteSize, Result};
+use super::{io, ByteSize, Context, Error, Result};
 use std::process::{Command, Output, Stdio};
 
-fn output(output: Output, cmd: String) -> Result<String> {
+/// `show_stdout` 为 false 时，不在日志中显示标准输出（用于查询大量数据）
+fn output(output: Output, cmd: String, show_stdout: bool) -> Result<String> {
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stdout = stdout.trim();
     let stderr = String::from_utf8_lossy(&output.stderr);
     let stderr = stderr.trim();
     if output.status.success() {
+        if !show_stdout {
+            info!(
+                "成功运行命令：{cmd}\n获取的字节数：{}",
+                ByteSize(stdout.len() as u64)
+            );
+            return Ok(stdout.to_owned());
+        }
         struct StdOutErr<'s> {
             stdout: &'s str,
             stderr: &'s str,
@@ -35,54 +43,111 @@ fn output(output: Output, cmd: String) -> Result<String> {
         );
         Ok(stdout.to_owned())
     } else {
-        bail!("{cmd} 运行失败\nstdout:\n{stdout}\nstderr:\n{stderr}")
+        bail!(
+            Database,
+            "{cmd} 运行失败\nstdout:\n{stdout}\nstderr:\n{stderr}"
+        )
     }
 }
 
-pub fn execute(sql: &str) -> Result<String> {
+/// 按全局配置得到表的完整名称，见 [`Config::table`](super::Config::table)
+pub fn table(name: &str) -> String {
+    super::config().table(name)
+}
+
+/// 按全局配置渲染 SQL 模板，见 [`Config::render`](super::Config::render)
+pub fn render(template: &str) -> String {
+    super::config().render(template)
+}
+
+/// 根据配置构建 clickhouse 客户端命令：连接参数不出现在日志中
+fn command(sql: &str) -> (Command, String) {
     const MULTI: &str = "--multiquery";
-    let mut cmd = Command::new("clickhouse-client");
-    cmd.args([MULTI, sql]);
-    let cmd_string = format!(r#"clickhouse-client "{MULTI}" "{sql}""#);
-    output(cmd.output()?, cmd_string)
+    let config = super::config();
+    let client = &config.clickhouse.client;
+    let mut cmd = Command::new(client);
+    cmd.args(config.clickhouse.args())
+        .envs(config.clickhouse.envs())
+        .args([MULTI, sql]);
+    (cmd, format!(r#"{client} "{MULTI}" "{sql}""#))
+}
+
+pub fn execute(sql: &str) -> Result<String> {
+    let (mut cmd, cmd_string) = command(sql);
+    let out = cmd
+        .output()
+        .or_err(Error::Database, || format!("无法运行 {cmd_string}"))?;
+    output(out, cmd_string, true)
+}
+
+/// 与 `execute` 相同，但查询结果不出现在日志中
+pub fn query(sql: &str) -> Result<String> {
+    let (mut cmd, cmd_string) = command(sql);
+    let out = cmd
+        .output()
+        .or_err(Error::Database, || format!("无法运行 {cmd_string}"))?;
+    output(out, cmd_string, false)
 }
 
 pub fn insert(sql: &str, reader: impl io::Read + io::Seek) -> Result<()> {
     use io::Seek;
-    const MULTI: &str = "--multiquery";
-    let mut cmd = Command::new("clickhouse-client");
+    let (mut cmd, cmd_string) = command(sql);
     cmd.stdin(Stdio::piped());
-    cmd.args([MULTI, sql]);
-    let cmd_string = format!(r#"clickhouse-client "{MULTI}" "{sql}""#);
-    let mut child = cmd.spawn()?;
+    let mut child = cmd
+        .spawn()
+        .or_err(Error::Database, || format!("无法运行 {cmd_string}"))?;
     if let Some(stdin) = child.stdin.as_mut() {
         let mut buf = io::BufReader::new(reader);
         let start = buf.stream_position().unwrap_or(0);
-        io::copy(&mut buf, stdin)?;
+        io::copy(&mut buf, stdin)
+            .or_err(Error::Database, || "无法向 clickhouse 传输数据".into())?;
         let end = buf.stream_position().unwrap_or(start);
         info!("成功向 clickhouse 插入了 {} 数据", ByteSize(end - start));
     } else {
-        bail!("无法打开 stdin 来传输 clickhouse 所需的数据");
+        bail!(Database, "无法打开 stdin 来传输 clickhouse 所需的数据");
     }
-    output(child.wait_with_output()?, cmd_string)?;
+    let out = child
+        .wait_with_output()
+        .or_err(Error::Database, || format!("无法等待 {cmd_string} 结束"))?;
+    output(out, cmd_string, true)?;
     Ok(())
 }
 
-pub fn insert_with_count_reported(table: &str, bytes: &[u8]) -> Result<()> {
+/// [`insert_with_count_reported`] 执行的录入和去重语句
+pub fn insert_statements(table: &str) -> [String; 2] {
+    [
+        format!("INSERT INTO {table} FORMAT CSV"),
+        format!("OPTIMIZE TABLE {table} DEDUPLICATE BY date, code"),
+    ]
+}
+
+/// 录入 csv 数据并去重，报告前后的条数；返回录入的行数和去重时去除的行数，
+/// 由 [`super::save_to_csv_and_clickhouse`] 在调用线程中记入下载记录
+pub fn insert_with_count_reported(table: &str, bytes: &[u8]) -> Result<(u64, u64)> {
     let sql_count = format!("SELECT count(*) FROM {table}");
     let count_old = execute(&sql_count)?;
     info!("{table} 现有数据 {count_old} 条");
-    let sql_insert_csv = format!("INSERT INTO {table} FORMAT CSV");
+    let [sql_insert_csv, sql_dedup] = insert_statements(table);
     insert(&sql_insert_csv, io::Cursor::new(bytes))?;
-    execute(&format!("OPTIMIZE TABLE {table} DEDUPLICATE BY date, code"))?;
+    execute(&sql_dedup)?;
     info!("{table} 已去重");
     let count_new = execute(&sql_count)?;
-    let added = count_new
+    let counts = count_new
         .parse::<u64>()
         .ok()
-        .zip(count_old.parse::<u64>().ok())
+        .zip(count_old.parse::<u64>().ok());
+    let added = counts
         .and_then(|(new, old)| new.checked_sub(old).map(|r| r.to_string()))
         .unwrap_or_default();
     info!("{table} 现有数据 {count_new} 条（增加了 {added} 条）");
-    Ok(())
+    // 每行 csv 数据以换行结尾
+    let rows = bytes.iter().filter(|&&b| b == b'\n').count() as u64;
+    let deduplicated = counts.map_or(0, |(new, old)| (old + rows).saturating_sub(new));
+    super::event::emit(&super::event::Event::RowsInserted {
+        table,
+        rows,
+        deduplicated,
+        total: counts.map(|(new, _)| new),
+    });
+    Ok((rows, deduplicated))
 }
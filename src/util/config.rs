@@ -0,0 +1,322 @@
+use crate::{error::Context, Error, Exchange, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+/// 配置文件（TOML）。读取顺序：
+/// 1. `--config` 指定的路径，或者环境变量 `CE_CONFIG` 指定的路径
+/// 2. `~/.config/ce/config.toml`（不存在时使用默认配置）
+/// 3. 以 `CE_` 开头的环境变量覆盖文件中的配置，见 [`Config::apply_env`]
+///
+/// ```toml
+/// cache_dir = "cache"
+/// database = "qihuo"
+/// table_prefix = ""
+/// sinks = ["csv", "clickhouse"]
+/// exchanges = ["czce", "dce"]
+///
+/// [clickhouse]
+/// client = "clickhouse-client"
+/// host = "127.0.0.1"
+/// port = 9000
+///
+/// [network]
+/// proxy = "http://127.0.0.1:7890"
+/// connect_timeout = 10
+/// timeout = 300
+/// jobs = 1
+/// per_host = 2
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// 缓存目录：保存下载和解析后的文件
+    pub cache_dir: PathBuf,
+    /// clickhouse 数据库名
+    pub database: String,
+    /// 表名前缀，如 `staging_` 使得表名为 `qihuo.staging_czce`
+    pub table_prefix: String,
+    pub clickhouse: ClickHouse,
+    pub network: Network,
+    /// 启用的数据保存方式
+    pub sinks: Vec<Sink>,
+    /// `ce status` 显示的交易所；下载和录入的命令不受影响
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cache_dir: PathBuf::from("cache"),
+            database: "qihuo".into(),
+            table_prefix: String::new(),
+            clickhouse: ClickHouse::default(),
+            network: Network::default(),
+            sinks: vec![Sink::Csv, Sink::Clickhouse],
+            exchanges: vec![Exchange::czce, Exchange::dce],
+        }
+    }
+}
+
+/// clickhouse 客户端的连接参数；未设置的参数使用 clickhouse-client 自身的默认值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClickHouse {
+    /// 客户端程序
+    pub client: String,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for ClickHouse {
+    fn default() -> Self {
+        ClickHouse {
+            client: "clickhouse-client".into(),
+            host: None,
+            port: None,
+            user: None,
+            password: None,
+        }
+    }
+}
+
+impl ClickHouse {
+    /// 传给客户端的连接参数
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::with_capacity(8);
+        let mut push = |key: &str, val: Option<String>| {
+            if let Some(val) = val {
+                args.push(format!("--{key}"));
+                args.push(val);
+            }
+        };
+        push("host", self.host.clone());
+        push("port", self.port.map(|p| p.to_string()));
+        push("user", self.user.clone());
+        args
+    }
+
+    /// 传给客户端的环境变量：密码通过 `CLICKHOUSE_PASSWORD` 传递，不出现在命令行参数中
+    pub fn envs(&self) -> Vec<(&'static str, String)> {
+        let password = self.password.iter();
+        password
+            .map(|p| ("CLICKHOUSE_PASSWORD", p.clone()))
+            .collect()
+    }
+}
+
+/// 网络请求设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Network {
+    /// 代理，如 `http://127.0.0.1:7890`、`socks5://127.0.0.1:1080`
+    pub proxy: Option<String>,
+    /// 连接超时（秒）
+    pub connect_timeout: u64,
+    /// 整个请求的超时（秒），0 表示不限制
+    pub timeout: u64,
+    /// 同时进行的下载数，1 表示逐个下载
+    pub jobs: usize,
+    /// 同一主机同时进行的下载数
+    pub per_host: usize,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network {
+            proxy: None,
+            connect_timeout: 10,
+            timeout: 300,
+            jobs: 1,
+            per_host: 2,
+        }
+    }
+}
+
+impl Network {
+    pub fn agent(&self) -> Result<ureq::Agent> {
+        let mut builder =
+            ureq::AgentBuilder::new().timeout_connect(Duration::from_secs(self.connect_timeout));
+        if self.timeout != 0 {
+            builder = builder.timeout(Duration::from_secs(self.timeout));
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = ureq::Proxy::new(proxy)
+                .or_err(Error::Config, || format!("无法识别代理 {proxy}"))?;
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder.build())
+    }
+}
+
+/// 数据保存方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sink {
+    /// 缓存目录下的 csv 文件
+    Csv,
+    /// clickhouse 数据库
+    Clickhouse,
+}
+
+impl std::str::FromStr for Sink {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "csv" => Sink::Csv,
+            "clickhouse" => Sink::Clickhouse,
+            _ => return Err(format!("{s} 不是保存方式，只支持 csv/clickhouse")),
+        })
+    }
+}
+
+impl Config {
+    /// 默认的配置文件路径：`~/.config/ce/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".config").join("ce").join("config.toml"))
+    }
+
+    /// 按照顺序读取配置：见 [`Config`] 的说明
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let env_path = std::env::var_os("CE_CONFIG").map(PathBuf::from);
+        let mut config = match path.map(Path::to_path_buf).or(env_path) {
+            // 指定的配置文件必须存在
+            Some(path) => Config::from_file(&path)?,
+            None => match Config::default_path().filter(|p| p.exists()) {
+                Some(path) => Config::from_file(&path)?,
+                None => Config::default(),
+            },
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config> {
+        let content = std::fs::read_to_string(path)
+            .or_err(Error::Config, || format!("无法读取 {}", path.display()))?;
+        let config = toml::from_str(&content)
+            .or_err(Error::Config, || format!("无法解析 {}", path.display()))?;
+        debug!("读取配置文件 {}", path.display());
+        Ok(config)
+    }
+
+    /// 环境变量覆盖配置：
+    /// `CE_CACHE_DIR`、`CE_DATABASE`、`CE_TABLE_PREFIX`、`CE_SINKS`（逗号分隔）、`CE_EXCHANGES`（逗号分隔）、
+    /// `CE_CLICKHOUSE_CLIENT`、`CE_CLICKHOUSE_HOST`、`CE_CLICKHOUSE_PORT`、`CE_CLICKHOUSE_USER`、
+    /// `CE_CLICKHOUSE_PASSWORD`、`CE_PROXY`、`CE_CONNECT_TIMEOUT`、`CE_TIMEOUT`、`CE_JOBS`、`CE_PER_HOST`
+    pub fn apply_env(&mut self) -> Result<()> {
+        fn var(key: &str) -> Option<String> {
+            std::env::var(key).ok().filter(|v| !v.is_empty())
+        }
+        fn parse<T: std::str::FromStr>(key: &str) -> Result<Option<T>>
+        where
+            T::Err: std::fmt::Debug,
+        {
+            var(key)
+                .map(|v| {
+                    v.parse()
+                        .or_err(Error::Config, || format!("环境变量 {key}={v} 无法解析"))
+                })
+                .transpose()
+        }
+        fn list<T: std::str::FromStr>(key: &str) -> Result<Option<Vec<T>>>
+        where
+            T::Err: std::fmt::Debug,
+        {
+            var(key)
+                .map(|v| {
+                    v.split(',')
+                        .map(|s| {
+                            s.trim()
+                                .parse()
+                                .or_err(Error::Config, || format!("环境变量 {key}={v} 无法解析"))
+                        })
+                        .collect()
+                })
+                .transpose()
+        }
+        if let Some(dir) = var("CE_CACHE_DIR") {
+            self.cache_dir = dir.into();
+        }
+        if let Some(database) = var("CE_DATABASE") {
+            self.database = database;
+        }
+        if let Some(prefix) = var("CE_TABLE_PREFIX") {
+            self.table_prefix = prefix;
+        }
+        if let Some(sinks) = list("CE_SINKS")? {
+            self.sinks = sinks;
+        }
+        if let Some(exchanges) = list("CE_EXCHANGES")? {
+            self.exchanges = exchanges;
+        }
+        let ch = &mut self.clickhouse;
+        if let Some(client) = var("CE_CLICKHOUSE_CLIENT") {
+            ch.client = client;
+        }
+        ch.host = var("CE_CLICKHOUSE_HOST").or(ch.host.take());
+        ch.port = parse("CE_CLICKHOUSE_PORT")?.or(ch.port);
+        ch.user = var("CE_CLICKHOUSE_USER").or(ch.user.take());
+        ch.password = var("CE_CLICKHOUSE_PASSWORD").or(ch.password.take());
+        let net = &mut self.network;
+        net.proxy = var("CE_PROXY").or(net.proxy.take());
+        if let Some(secs) = parse("CE_CONNECT_TIMEOUT")? {
+            net.connect_timeout = secs;
+        }
+        if let Some(secs) = parse("CE_TIMEOUT")? {
+            net.timeout = secs;
+        }
+        if let Some(jobs) = parse("CE_JOBS")? {
+            net.jobs = jobs;
+        }
+        if let Some(n) = parse("CE_PER_HOST")? {
+            net.per_host = n;
+        }
+        Ok(())
+    }
+
+    pub fn sink_enabled(&self, sink: Sink) -> bool {
+        self.sinks.contains(&sink)
+    }
+
+    /// 表的完整名称：`{database}.{table_prefix}{name}`，如 `qihuo.czce`
+    pub fn table(&self, name: &str) -> String {
+        format!("{}.{}{name}", self.database, self.table_prefix)
+    }
+
+    /// 替换 SQL 模板中的 `{database}` 和 `{prefix}`（表名前缀）
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{database}", &self.database)
+            .replace("{prefix}", &self.table_prefix)
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// 设置全局配置：必须在第一次调用 `config` 或 `init_data` 之前设置，否则返回错误；
+/// 配置无效（如无法识别的代理、并发数为 0）时返回 [`Error::Config`]
+pub fn set_config(config: Config) -> Result<()> {
+    let net = &config.network;
+    ensure!(
+        net.jobs != 0 && net.per_host != 0,
+        Config,
+        "network.jobs 和 network.per_host 至少为 1"
+    );
+    net.agent()?;
+    CONFIG
+        .set(config)
+        .map_err(|_| err!(Config, "配置已经初始化，无法再次设置"))
+}
+
+/// 全局配置：未通过 `set_config` 设置时使用默认配置；
+/// 需要配置文件和环境变量时，先用 [`Config::load`] 读取，再调用 [`set_config`]
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,179 @@
+//! 结构化事件：`--output json` 时每个事件以一行 JSON 写到标准输出，供调度程序解析；
+//! 默认的 text 输出不产生事件，仍由 simplelog 在终端显示日志。
+//!
+//! 每行都有 `event`（事件类型）和 `time`（UTC+8）字段，最后一行为 `result`。
+use super::Encoding;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+use time::OffsetDateTime;
+
+/// 命令行的输出方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    /// 终端日志（默认）
+    #[default]
+    Text,
+    /// JSON lines 事件
+    Json,
+}
+
+impl std::str::FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            _ => Err(format!("{s} 不是输出方式，只支持 text/json")),
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// 设置输出方式；应在下载、录入之前设置
+pub fn set_output(output: Output) {
+    JSON.store(output == Output::Json, Ordering::Relaxed);
+}
+
+/// 是否以 JSON 输出事件
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// 错误的类型和说明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorInfo {
+    /// [`crate::Error`] 的变体名，如 `Network`；非库函数的错误为 `Other`，error 级别的日志为 `Logged`
+    pub kind: String,
+    pub message: String,
+}
+
+/// 一个事件
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// 开始下载
+    FetchStarted {
+        url: &'a str,
+    },
+    /// 下载完成；`status` 为 404 时文件不存在
+    FetchFinished {
+        url: &'a str,
+        status: u16,
+        bytes: u64,
+        elapsed_ms: u64,
+    },
+    /// 文件解析完成（见 [`super::manifest::Entry`]）
+    FileParsed {
+        url: &'a str,
+        files: &'a [String],
+        encoding: Option<Encoding>,
+        parsed: u64,
+        rejected: u64,
+        table: Option<&'a str>,
+    },
+    /// 录入 clickhouse；`total` 为录入并去重后表中的条数
+    RowsInserted {
+        table: &'a str,
+        rows: u64,
+        deduplicated: u64,
+        total: Option<u64>,
+    },
+    Warning {
+        message: String,
+    },
+    Error(ErrorInfo),
+    /// 最后的结果：成功时 `data` 为子命令的结果，失败时 `error` 为错误
+    Result {
+        command: &'a str,
+        ok: bool,
+        elapsed_ms: u64,
+        data: Option<serde_json::Value>,
+        error: Option<ErrorInfo>,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    #[serde(with = "time::serde::rfc3339")]
+    time: OffsetDateTime,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// 以 JSON 输出时，把事件写到标准输出；否则什么也不做
+pub fn emit(event: &Event) {
+    if !json() {
+        return;
+    }
+    let line = Line {
+        time: OffsetDateTime::now_utc().to_offset(time::macros::offset!(+8)),
+        event,
+    };
+    let mut stdout = std::io::stdout().lock();
+    // 标准输出被关闭时无法报告，忽略
+    if serde_json::to_writer(&mut stdout, &line).is_ok() {
+        _ = stdout.write_all(b"\n");
+        _ = stdout.flush();
+    }
+}
+
+static DATA: Mutex<Option<serde_json::Value>> = Mutex::new(None);
+
+/// 设置子命令的结果，作为 `result` 事件的 `data`
+pub fn set_data(data: &impl Serialize) {
+    match serde_json::to_value(data) {
+        Ok(value) => *DATA.lock().unwrap() = Some(value),
+        Err(err) => error!("结果无法序列化为 JSON：{err}"),
+    }
+}
+
+/// 取出子命令的结果
+pub fn take_data() -> Option<serde_json::Value> {
+    DATA.lock().unwrap().take()
+}
+
+/// 把 warn、error 级别的日志作为事件输出，与终端日志一起使用（见 [`super::init_json_log`]）
+pub(crate) struct EventLogger;
+
+impl log::Log for EventLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        let message = record.args().to_string();
+        match record.level() {
+            log::Level::Error => emit(&Event::Error(ErrorInfo {
+                kind: "Logged".into(),
+                message,
+            })),
+            log::Level::Warn => emit(&Event::Warning { message }),
+            _ => (),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl simplelog::SharedLogger for EventLogger {
+    fn level(&self) -> log::LevelFilter {
+        log::LevelFilter::Warn
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn log::Log> {
+        Box::new(*self)
+    }
+}
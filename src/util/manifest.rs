@@ -0,0 +1,232 @@
+//! 下载记录：每次下载（`fetch`、`fetch_zip` 等）的来源和处理结果，
+//! 以 JSON lines 追加到缓存目录下的 [`FILE`]，启用 clickhouse 时同时录入 `{database}.{prefix}_ce_ingest`。
+//!
+//! 下载时先记录在当前线程中，解析和录入的过程中补充编码、条数等，最后由 [`finish`] 写入；
+//! 中途出错时由 [`guard`] 写入并标记错误。本次运行写入的记录可由 [`finished`] 取出，用于结束时的汇总。
+use super::{clickhouse, config, event, init_data, Encoding, Sink};
+use crate::{error::Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{cell::RefCell, io::Write, time::Instant};
+use time::OffsetDateTime;
+
+/// 缓存目录下的下载记录文件名
+pub const FILE: &str = "manifest.jsonl";
+/// 下载记录的表名
+pub const TABLE: &str = "_ce_ingest";
+
+/// 一次下载的记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    /// 下载完成的时间
+    #[serde(with = "time::serde::rfc3339")]
+    pub fetched_at: OffsetDateTime,
+    pub bytes: u64,
+    /// 下载内容的 SHA-256（小写十六进制）
+    pub sha256: String,
+    /// 文本内容的编码；xlsx 等二进制文件为 None
+    pub encoding: Option<Encoding>,
+    /// zip 文件中的文件名
+    pub files: Vec<String>,
+    /// 解析成功的行数
+    pub parsed: u64,
+    /// 无法解析而跳过的行数
+    pub rejected: u64,
+    /// 录入的表；只下载不录入时为 None
+    pub table: Option<String>,
+    /// 录入 clickhouse 的行数；未录入时为 None
+    #[serde(default)]
+    pub inserted: Option<u64>,
+    /// 录入后 `OPTIMIZE ... DEDUPLICATE` 去除的重复行数；未录入时为 None
+    #[serde(default)]
+    pub deduplicated: Option<u64>,
+    /// 从开始下载到录入完成的毫秒数
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    /// 下载之后解析或者录入出错时为 Some，此时 `table` 为 None（见 [`guard`]）
+    #[serde(default)]
+    pub error: Option<String>,
+    /// 开始下载的时间，用于计算 `elapsed_ms`
+    #[serde(skip)]
+    pub started: Option<Instant>,
+}
+
+impl Entry {
+    pub fn new(url: &str, bytes: &[u8]) -> Entry {
+        Entry {
+            url: url.to_owned(),
+            fetched_at: OffsetDateTime::now_utc().to_offset(time::macros::offset!(+8)),
+            bytes: bytes.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(bytes)),
+            encoding: None,
+            files: Vec::new(),
+            parsed: 0,
+            rejected: 0,
+            table: None,
+            inserted: None,
+            deduplicated: None,
+            elapsed_ms: 0,
+            error: None,
+            started: None,
+        }
+    }
+}
+
+thread_local! {
+    static PENDING: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+    static FINISHED: RefCell<Vec<Entry>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 修改当前线程最近一次下载的记录；没有记录时什么也不做
+fn update(f: impl FnOnce(&mut Entry)) {
+    PENDING.with_borrow_mut(|pending| {
+        if let Some(entry) = pending.last_mut() {
+            f(entry);
+        }
+    });
+}
+
+/// 记录一次下载，`started` 为开始下载的时间
+pub fn record(url: &str, bytes: &[u8], started: Instant) {
+    let entry = Entry {
+        started: Some(started),
+        ..Entry::new(url, bytes)
+    };
+    PENDING.with_borrow_mut(|pending| pending.push(entry));
+}
+
+/// 补充编码
+pub fn encoding(encoding: Encoding) {
+    update(|e| e.encoding = Some(encoding));
+}
+
+/// 补充 zip 文件中的文件名
+pub fn file(name: &str) {
+    update(|e| e.files.push(name.to_owned()));
+}
+
+/// 累加解析成功和跳过的行数
+pub fn count(parsed: u64, rejected: u64) {
+    update(|e| {
+        e.parsed += parsed;
+        e.rejected += rejected;
+    });
+}
+
+/// 补充录入 clickhouse 的行数和去重时去除的行数（累加）
+pub fn inserted(rows: u64, deduplicated: u64) {
+    update(|e| {
+        *e.inserted.get_or_insert(0) += rows;
+        *e.deduplicated.get_or_insert(0) += deduplicated;
+    });
+}
+
+/// 取出当前线程尚未写入的记录，标记录入的表，并写入下载记录文件和表
+pub fn finish(table: Option<&str>) -> Result<Vec<Entry>> {
+    let mut entries = PENDING.take();
+    if entries.is_empty() {
+        return Ok(entries);
+    }
+    for entry in &mut entries {
+        entry.table = table.map(str::to_owned);
+        event::emit(&event::Event::FileParsed {
+            url: &entry.url,
+            files: &entry.files,
+            encoding: entry.encoding,
+            parsed: entry.parsed,
+            rejected: entry.rejected,
+            table,
+        });
+    }
+    write(entries)
+}
+
+/// 在处理一个文件之前创建，被丢弃时若当前线程还有尚未写入的记录（即出错而没有调用 [`finish`]），
+/// 则写入这些记录并标记错误，使它们不会被计入下一个文件
+#[must_use]
+pub struct Guard(());
+
+/// 见 [`Guard`]
+pub fn guard() -> Guard {
+    Guard(())
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let mut entries = PENDING.take();
+        if entries.is_empty() {
+            return;
+        }
+        for entry in &mut entries {
+            entry.error = Some("下载之后解析或者录入出错，未完成".to_owned());
+        }
+        // 原来的错误会返回给调用者，这里只记录日志
+        if let Err(err) = write(entries) {
+            warn!("无法写入出错的下载记录：{err}");
+        }
+    }
+}
+
+/// 计算耗时，并写入下载记录文件和表
+fn write(mut entries: Vec<Entry>) -> Result<Vec<Entry>> {
+    for entry in &mut entries {
+        if let Some(started) = entry.started {
+            entry.elapsed_ms = started.elapsed().as_millis() as u64;
+        }
+    }
+    let mut jsonl = Vec::with_capacity(entries.len() * 256);
+    for entry in &entries {
+        serde_json::to_writer(&mut jsonl, entry)
+            .or_err(Error::Manifest, || format!("{entry:?} 无法序列化"))?;
+        jsonl.push(b'\n');
+    }
+    let path = init_data().cache_dir.join(FILE);
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(&jsonl)?;
+    debug!("{} 追加了 {} 条下载记录", path.display(), entries.len());
+    if config().sink_enabled(Sink::Clickhouse) {
+        super::migrate::migrate_once()?;
+        clickhouse::insert(&insert_sql(), std::io::Cursor::new(jsonl))?;
+    }
+    FINISHED.with_borrow_mut(|finished| finished.extend(entries.iter().cloned()));
+    Ok(entries)
+}
+
+/// 取出当前线程在本次运行中已写入的记录
+pub fn finished() -> Vec<Entry> {
+    FINISHED.take()
+}
+
+/// 录入下载记录的语句
+pub fn insert_sql() -> String {
+    format!(
+        "INSERT INTO {} SETTINGS date_time_input_format = 'best_effort' FORMAT JSONEachRow",
+        clickhouse::table(TABLE)
+    )
+}
+
+/// 解析下载记录文件的内容
+pub fn parse(jsonl: &str) -> Result<Vec<Entry>> {
+    jsonl
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .or_err(Error::Manifest, || format!("{line} 无法解析为下载记录"))
+        })
+        .collect()
+}
+
+/// 读取缓存目录下的下载记录，按写入顺序排列；文件不存在时为空
+pub fn history() -> Result<Vec<Entry>> {
+    let path = init_data().cache_dir.join(FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(jsonl) => parse(&jsonl),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
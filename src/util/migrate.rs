@@ -0,0 +1,182 @@
+//! clickhouse 表结构的迁移：按版本号依次执行 `src/sql/migrations` 下的 SQL，
+//! 并在 `{database}.{prefix}_ce_migrations` 表中记录已执行的版本。
+//!
+//! clickhouse 没有事务，因此每个迁移应当可以重复执行（`IF NOT EXISTS` 等）。
+use super::clickhouse::{execute, render, table};
+use crate::{error::Context, Error, Result};
+use std::sync::Mutex;
+
+/// 一个迁移步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    /// 版本号：严格递增
+    pub version: u32,
+    pub name: &'static str,
+    /// SQL 模板，见 [`render`]
+    pub sql: &'static str,
+}
+
+/// 所有迁移步骤，按版本号排列；新的迁移只能追加在末尾
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "czce",
+        sql: include_str!("../sql/migrations/0001_czce.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "dce",
+        sql: include_str!("../sql/migrations/0002_dce.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "dce_prev_close",
+        sql: include_str!("../sql/migrations/0003_dce_prev_close.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "dce_dsp",
+        sql: include_str!("../sql/migrations/0004_dce_dsp.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "ce_main",
+        sql: include_str!("../sql/migrations/0005_ce_main.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "ce_continuous",
+        sql: include_str!("../sql/migrations/0006_ce_continuous.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "ce_index",
+        sql: include_str!("../sql/migrations/0007_ce_index.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "ce_ingest",
+        sql: include_str!("../sql/migrations/0008_ce_ingest.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "ce_ingest_summary",
+        sql: include_str!("../sql/migrations/0009_ce_ingest_summary.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "ce_continuous_volume",
+        sql: include_str!("../sql/migrations/0010_ce_continuous_volume.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "ce_ingest_error",
+        sql: include_str!("../sql/migrations/0011_ce_ingest_error.sql"),
+    },
+];
+
+/// 记录已执行迁移的表名
+pub const TABLE: &str = "_ce_migrations";
+
+/// 已执行的迁移
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Applied {
+    pub version: u32,
+    pub name: String,
+    pub applied_at: String,
+}
+
+/// 创建数据库和记录已执行迁移的表
+pub fn bookkeeping_sql() -> String {
+    render(&format!(
+        "CREATE DATABASE IF NOT EXISTS {{database}};
+CREATE TABLE IF NOT EXISTS {{database}}.{{prefix}}{TABLE} (
+  version    UInt32   COMMENT '迁移版本',
+  name       String   COMMENT '迁移名称',
+  applied_at DateTime COMMENT '执行时间'
+) ENGINE = MergeTree
+ORDER BY version;"
+    ))
+}
+
+/// 记录迁移已执行
+pub fn record_sql(m: &Migration) -> String {
+    format!(
+        "INSERT INTO {} VALUES ({}, '{}', now())",
+        table(TABLE),
+        m.version,
+        m.name
+    )
+}
+
+/// 解析 `SELECT version, name, applied_at` 的 TSV 输出：按版本排列；
+/// 多个进程同时迁移时同一版本可能被记录多次，只保留最早的一条
+pub fn parse_applied(tsv: &str) -> Result<Vec<Applied>> {
+    let mut applied = tsv
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split('\t');
+            let mut next = || {
+                fields
+                    .next()
+                    .or_err(Error::Database, || format!("无法解析迁移记录 {line:?}"))
+            };
+            let version = next()?;
+            let version = version
+                .parse()
+                .or_err(Error::Database, || format!("无法解析迁移版本 {version:?}"))?;
+            Ok(Applied {
+                version,
+                name: next()?.to_owned(),
+                applied_at: next()?.to_owned(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    applied.sort_by(|a, b| (a.version, &a.applied_at).cmp(&(b.version, &b.applied_at)));
+    applied.dedup_by_key(|a| a.version);
+    Ok(applied)
+}
+
+/// 查询已执行的迁移；记录表不存在时视为没有执行过任何迁移
+pub fn applied() -> Result<Vec<Applied>> {
+    let table = table(TABLE);
+    if execute(&format!("EXISTS TABLE {table}"))?.trim() != "1" {
+        return Ok(Vec::new());
+    }
+    parse_applied(&execute(&format!(
+        "SELECT version, name, applied_at FROM {table} ORDER BY version"
+    ))?)
+}
+
+/// 尚未执行的迁移
+pub fn pending(applied: &[Applied]) -> impl Iterator<Item = &'static Migration> + '_ {
+    MIGRATIONS
+        .iter()
+        .filter(move |m| !applied.iter().any(|a| a.version == m.version))
+}
+
+/// 依次执行所有尚未执行的迁移，返回本次执行的迁移
+pub fn migrate() -> Result<Vec<&'static Migration>> {
+    execute(&bookkeeping_sql())?;
+    let applied = applied()?;
+    let mut done = Vec::new();
+    for m in pending(&applied) {
+        info!("执行迁移 {:04}_{}", m.version, m.name);
+        execute(&render(m.sql))?;
+        execute(&record_sql(m))?;
+        done.push(m);
+    }
+    Ok(done)
+}
+
+/// 在本进程中第一次成功执行 [`migrate`] 之后不再执行：录入、记录下载之前调用，避免每次都查询迁移记录
+pub fn migrate_once() -> Result<()> {
+    static DONE: Mutex<bool> = Mutex::new(false);
+    let mut done = DONE.lock().unwrap();
+    if !*done {
+        migrate()?;
+        *done = true;
+    }
+    Ok(())
+}
@@ -1,36 +1,67 @@
-use crate::{dce, Result};
+use crate::{dce, error::Context, Error, Result};
 use bytesize::ByteSize;
 use regex::Regex;
-use serde::{Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use simplelog::{
-    ColorChoice, Config, ConfigBuilder, LevelFilter, SimpleLogger, TermLogger, TerminalMode,
+    ColorChoice, CombinedLogger, Config as LogConfig, ConfigBuilder, LevelFilter, SimpleLogger,
+    TermLogger, TerminalMode,
 };
 use std::{
     borrow::Cow,
     fs::File,
-    io::{self, Cursor, ErrorKind, Write},
+    io::{self, Cursor, Write},
     path::{Path, PathBuf},
     sync::OnceLock,
+    time::Instant,
 };
 use time::{format_description::FormatItem, macros::format_description, Date, OffsetDateTime};
 
 pub mod clickhouse;
+pub mod config;
+pub mod event;
+pub mod manifest;
+pub mod migrate;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod prefetch;
+pub mod progress;
+pub mod year;
+pub use config::{config, set_config, Config, Sink};
 
 /// 开启日志
 pub fn init_log() -> Result<()> {
+    let (level, config) = log_config()?;
+    let term = TermLogger::new(level, config, TerminalMode::Mixed, ColorChoice::Auto);
+    CombinedLogger::init(vec![Box::new(progress::Suspend(term))])
+        .or_err(Error::Log, || "无法设置终端日志".into())
+}
+
+/// 以 JSON 输出事件时开启日志：终端日志只写到标准错误，warn、error 级别的日志同时作为事件输出
+pub fn init_json_log() -> Result<()> {
+    let (level, config) = log_config()?;
+    CombinedLogger::init(vec![
+        Box::new(progress::Suspend(TermLogger::new(
+            level,
+            config,
+            TerminalMode::Stderr,
+            ColorChoice::Auto,
+        ))),
+        Box::new(event::EventLogger),
+    ])
+    .or_err(Error::Log, || "无法设置终端日志".into())
+}
+
+/// 日志级别（环境变量 `LOG`，默认为 info）和时区
+fn log_config() -> Result<(LevelFilter, LogConfig)> {
     let level = std::env::var("LOG").map_or_else(
         |_| LevelFilter::Info,
         |l| l.parse().unwrap_or(LevelFilter::Off),
     );
     let mut config = ConfigBuilder::new();
-    config.set_time_offset(time::UtcOffset::from_hms(8, 0, 0)?);
-    TermLogger::init(
-        level,
-        config.build(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )?;
-    Ok(())
+    config.set_time_offset(
+        time::UtcOffset::from_hms(8, 0, 0).or_err(Error::Log, || "无法设置时区".into())?,
+    );
+    Ok((level, config.build()))
 }
 
 /// 测试函数的日志
@@ -40,7 +71,7 @@ pub fn init_test_log() -> &'static Init {
         |_| LevelFilter::Off,
         |l| l.parse().unwrap_or(LevelFilter::Off),
     );
-    if SimpleLogger::init(level, Config::default()).is_err() {
+    if SimpleLogger::init(level, LogConfig::default()).is_err() {
         error!("日志开启失败，或许已经设置了日志");
     }
     init_data()
@@ -55,41 +86,172 @@ pub fn display_option<T: std::fmt::Display>(t: &Option<T>) -> String {
 }
 
 pub struct Init {
+    pub config: &'static Config,
     pub cache_dir: PathBuf,
     pub regex_czce: Regex,
     pub this_year: u16,
     pub links_dce: dce::DownloadLinks,
+    /// 根据网络配置（代理、超时）构建的 HTTP 客户端
+    pub agent: ureq::Agent,
 }
 
+/// 根据全局配置初始化；需要自定义配置时，先调用 [`set_config`]
 pub fn init_data() -> &'static Init {
     static DATA: OnceLock<Init> = OnceLock::new();
-    DATA.get_or_init(|| Init {
-        cache_dir: cache_dir().unwrap(),
-        regex_czce: Regex::new(",| ").unwrap(),
-        this_year: OffsetDateTime::now_utc()
-            .to_offset(time::macros::offset!(+8))
-            .year()
-            .try_into()
-            .unwrap(),
-        links_dce: dce::DownloadLinks::new_static().unwrap(),
+    DATA.get_or_init(|| {
+        let config = config();
+        let cache_dir = cache_dir(&config.cache_dir).unwrap();
+        // 优先使用 `ce dce links refresh` 刷新过的下载链接
+        let links_dce = dce::DownloadLinks::new_cached(&cache_dir)
+            .unwrap_or_else(|err| {
+                warn!("无法读取刷新过的下载链接，使用内置的链接：{err}");
+                None
+            })
+            .unwrap_or_else(|| dce::DownloadLinks::new_static().unwrap());
+        Init {
+            config,
+            cache_dir,
+            regex_czce: Regex::new(",| ").unwrap(),
+            this_year: OffsetDateTime::now_utc()
+                .to_offset(time::macros::offset!(+8))
+                .year()
+                .try_into()
+                .unwrap(),
+            links_dce,
+            agent: config.network.agent().unwrap(),
+        }
     })
 }
 
+/// 合约代码开头的字母部分，即品种代码：`MA401` -> `MA`，`v2201` -> `v`
+pub fn product(code: &str) -> &str {
+    let end = code
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(code.len());
+    &code[..end]
+}
+
+/// 合约的交割年月 `(年, 月)`：
+/// * 大连交易所为 4 位数字，如 `v2201` -> `(2022, 1)`
+/// * 郑州交易所为 3 位数字，年份只有个位，如 `MA401`：取不早于交易日 `date` 所在年份的最近一年，
+///   即 2023 年交易时为 `(2024, 1)`
+///
+/// 无法解析时返回 None。
+pub fn delivery(code: &str, date: time::Date) -> Option<(i32, u8)> {
+    let digits = &code[product(code).len()..];
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (year, month) = match digits.len() {
+        4 => (2000 + digits[..2].parse::<i32>().ok()?, &digits[2..]),
+        3 => {
+            let unit = digits[..1].parse::<i32>().ok()?;
+            let year = date.year() - date.year().rem_euclid(10) + unit;
+            (
+                if year < date.year() { year + 10 } else { year },
+                &digits[1..],
+            )
+        }
+        _ => return None,
+    };
+    let month = month.parse::<u8>().ok().filter(|m| (1..=12).contains(m))?;
+    Some((year, month))
+}
+
 pub type Response = Result<Cursor<Vec<u8>>>;
 
+/// GET 请求；优先使用后台下载的结果（见 [`prefetch`]）
 pub fn fetch(url: &str) -> Response {
-    let mut buf = Vec::with_capacity(1024 * 1024 * 4);
-    ureq::get(url).call()?.into_reader().read_to_end(&mut buf)?;
-    info!("{url} 获取的字节数：{}", ByteSize(buf.len() as u64));
-    Ok(Cursor::new(buf))
+    let started = fetch_started(url);
+    match prefetched(url, started)? {
+        Some(Some(resp)) => return Ok(resp),
+        Some(None) => bail!(Network, "{url} 请求失败：404"),
+        None => (),
+    }
+    let resp = init_data().agent.get(url).call();
+    read_response(url, started, resp)
+}
+
+/// 与 `fetch` 相同，但 404 时返回 `Ok(None)`：用于按日发布、非交易日不存在的文件
+pub fn fetch_if_exists(url: &str) -> Result<Option<Cursor<Vec<u8>>>> {
+    let started = fetch_started(url);
+    if let Some(resp) = prefetched(url, started)? {
+        return Ok(resp);
+    }
+    match init_data().agent.get(url).call() {
+        Err(ureq::Error::Status(404, _)) => {
+            not_found(url, started);
+            Ok(None)
+        }
+        resp => read_response(url, started, resp).map(Some),
+    }
+}
+
+/// 以表单的形式 POST 请求
+pub fn fetch_form(url: &str, form: &[(&str, &str)]) -> Response {
+    let started = fetch_started(url);
+    let resp = init_data().agent.post(url).send_form(form);
+    read_response(url, started, resp)
+}
+
+fn fetch_started(url: &str) -> Instant {
+    event::emit(&event::Event::FetchStarted { url });
+    Instant::now()
+}
+
+fn not_found(url: &str, started: Instant) {
+    debug!("{url} 不存在");
+    event::emit(&event::Event::FetchFinished {
+        url,
+        status: 404,
+        bytes: 0,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    });
+}
+
+/// 下载完成：记录日志、下载记录和事件
+fn fetched(url: &str, buf: Vec<u8>, started: Instant) -> Cursor<Vec<u8>> {
+    let bytes = buf.len() as u64;
+    info!("{url} 获取的字节数：{}", ByteSize(bytes));
+    manifest::record(url, &buf, started);
+    event::emit(&event::Event::FetchFinished {
+        url,
+        status: 200,
+        bytes,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    });
+    Cursor::new(buf)
+}
+
+/// 后台下载的结果：`None` 表示不在后台下载的范围内，`Some(None)` 表示 404
+fn prefetched(url: &str, started: Instant) -> Result<Option<Option<Cursor<Vec<u8>>>>> {
+    Ok(match prefetch::take(url) {
+        None => None,
+        Some(prefetch::Fetched::NotFound) => {
+            not_found(url, started);
+            Some(None)
+        }
+        Some(prefetch::Fetched::Failed(err)) => bail!(Network, "{url} 请求失败：{err}"),
+        // 从后台开始下载时计时，而不是取用时
+        Some(prefetch::Fetched::Body(buf, started)) => Some(Some(fetched(url, buf, started))),
+    })
+}
+
+fn read_response(
+    url: &str,
+    started: Instant,
+    resp: Result<ureq::Response, ureq::Error>,
+) -> Response {
+    let resp = resp.or_err(Error::Network, || format!("{url} 请求失败"))?;
+    let buf =
+        progress::read_body(url, resp).or_err(Error::Network, || format!("{url} 读取响应失败"))?;
+    Ok(fetched(url, buf, started))
 }
 
 pub fn parse_date_czce<'de, D: Deserializer<'de>>(d: D) -> Result<Date, D::Error> {
     const FMT: &[FormatItem<'static>] = format_description!("[year]-[month]-[day]");
     let s = <&str>::deserialize(d)?;
-    Ok(Date::parse(s, FMT)
-        .map_err(|err| format!("{s:?} 无法解析成日期：{err:?}"))
-        .unwrap())
+    Date::parse(s, FMT).map_err(|err| de::Error::custom(format!("{s:?} 无法解析成日期：{err:?}")))
 }
 
 pub fn parse_option_f32<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f32>, D::Error> {
@@ -99,8 +261,7 @@ pub fn parse_option_f32<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f32>,
     } else {
         let float = s
             .parse()
-            .map_err(|err| format!("{s:?} 无法解析为 f32：{err:?}"))
-            .unwrap();
+            .map_err(|err| de::Error::custom(format!("{s:?} 无法解析为 f32：{err:?}")))?;
         Ok(Some(float))
     }
 }
@@ -109,15 +270,15 @@ pub fn parse_u32_from_f32<'de, D: Deserializer<'de>>(d: D) -> Result<u32, D::Err
     let s = <&str>::deserialize(d)?;
     let float: f32 = s
         .parse()
-        .map_err(|_| format!("{s} 无法解析为 f32"))
-        .unwrap();
+        .map_err(|_| de::Error::custom(format!("{s} 无法解析为 f32")))?;
     if float < 0.0 {
-        panic!("{s} 无法从 f32 转化为 u32")
+        Err(de::Error::custom(format!("{s} 无法从 f32 转化为 u32")))
     } else {
         Ok(float as _)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Encoding {
     UTF8,
     GBK,
@@ -134,17 +295,20 @@ pub fn fetch_zip(
             let file = init_data().cache_dir.join("failed");
             File::create(&file)?.write_all(fetched.get_ref())?;
             bail!(
+                Archive,
                 "无法解析 zip 文件，下载的内容保存在 {}：{err:?}",
                 file.display()
             );
         }
     };
     for i in 0..zipped.len() {
-        let mut unzipped = zipped.by_index(i)?;
+        let mut unzipped = zipped
+            .by_index(i)
+            .or_err(Error::Archive, || format!("{url} 无法读取第 {i} 个文件"))?;
         if unzipped.is_file() {
             let unzipped_path = unzipped
                 .enclosed_name()
-                .ok_or_else(|| eyre!("`{}` 无法转成 &Path", unzipped.name()))?;
+                .ok_or_else(|| err!(Archive, "`{}` 无法转成 &Path", unzipped.name()))?;
             let size = unzipped.size();
             let unzipped_path_display = unzipped_path.display().to_string();
             info!(
@@ -155,17 +319,42 @@ pub fn fetch_zip(
             let file_name = unzipped_path
                 .file_name()
                 .and_then(|fname| Some(fname.to_str()?.to_owned()))
-                .ok_or_else(|| eyre!("无法从 {unzipped_path:?} 中获取文件名"))?;
+                .ok_or_else(|| err!(Archive, "无法从 {unzipped_path:?} 中获取文件名"))?;
             let mut buf = Vec::with_capacity(size as usize);
-            io::copy(&mut unzipped, &mut buf)?;
+            io::copy(&mut unzipped, &mut buf).or_err(Error::Archive, || {
+                format!("无法解压 {unzipped_path_display}")
+            })?;
+            manifest::file(&file_name);
             handle_unzipped(buf, file_name)?;
         } else {
-            bail!("{} 还未实现解压成文件夹", unzipped.name());
+            bail!(Archive, "{} 还未实现解压成文件夹", unzipped.name());
         }
     }
     Ok(())
 }
 
+/// 下载 zip 文件并解压所有文件：`(文件内容, 文件名)`
+pub fn unzip(url: &str) -> Result<Vec<(Vec<u8>, String)>> {
+    let mut files = Vec::with_capacity(1);
+    fetch_zip(url, |raw, fname| {
+        files.push((raw, fname));
+        Ok(())
+    })?;
+    Ok(files)
+}
+
+/// 把准备阶段的错误作为迭代器的唯一元素，从而直接返回 `impl Iterator`
+pub(crate) fn try_iter<T, I>(iter: Result<I>) -> impl Iterator<Item = Result<T>>
+where
+    I: Iterator<Item = Result<T>>,
+{
+    let (iter, err) = match iter {
+        Ok(iter) => (Some(iter), None),
+        Err(err) => (None, Some(Err(err))),
+    };
+    err.into_iter().chain(iter.into_iter().flatten())
+}
+
 /// 处理编码
 pub fn read_txt<'a>(buf: &'a [u8], src: &str) -> Result<(Cow<'a, str>, Encoding)> {
     let content_encoding = match std::str::from_utf8(buf) {
@@ -175,31 +364,28 @@ pub fn read_txt<'a>(buf: &'a [u8], src: &str) -> Result<(Cow<'a, str>, Encoding)
             info!("{src} 不是 UTF8 编码的，尝试使用 GBK 解码");
             let (cow, encoding, err) = gbk.decode(buf);
             if err {
-                bail!("{src} 不是 GBK 编码的，需要手动确认编码");
+                bail!(Encoding, "{src} 不是 GBK 编码的，需要手动确认编码");
             } else if encoding != gbk {
-                bail!("{src} GBK/{encoding:?} 解码失败");
+                bail!(Encoding, "{src} GBK/{encoding:?} 解码失败");
             }
             (cow, Encoding::GBK)
         }
     };
+    manifest::encoding(content_encoding.1);
     Ok(content_encoding)
 }
 
-/// 缓存目录
-pub fn cache_dir() -> Result<PathBuf> {
-    const CACHE: &str = "cache";
-    let dir = PathBuf::from(CACHE);
-    match std::fs::create_dir(&dir) {
-        Ok(_) => debug!("成功创建 {CACHE} 目录"),
+/// 缓存目录：不存在时创建
+pub fn cache_dir(dir: &Path) -> Result<PathBuf> {
+    let display = dir.display();
+    match std::fs::create_dir_all(dir) {
+        Ok(_) => debug!("{display} 目录已存在或已创建"),
         Err(err) => {
-            if matches!(err.kind(), ErrorKind::AlreadyExists) {
-                debug!("{CACHE} 已存在");
-            } else {
-                bail!("无法创建 {CACHE}，因为 {err:?}");
-            }
+            error!("无法创建 {display}，因为 {err:?}");
+            return Err(err.into());
         }
     }
-    Ok(dir)
+    Ok(dir.to_owned())
 }
 
 pub fn save_csv(bytes: &[u8], filename: impl AsRef<Path>) -> Result<PathBuf> {
@@ -217,21 +403,34 @@ pub fn save_csv(bytes: &[u8], filename: impl AsRef<Path>) -> Result<PathBuf> {
     Ok(path)
 }
 
+/// 同时保存 csv 文件和录入 clickhouse（按启用的 [`Sink`]）。`ch` 返回录入的行数和去重时去除的行数，
+/// 在当前线程中记入下载记录：记录是线程局部的，不能在录入的线程中补充
 pub fn save_to_csv_and_clickhouse<F, G>(csv: F, ch: G) -> Result<()>
 where
     F: Send + FnOnce() -> Result<PathBuf>,
-    G: Send + FnOnce() -> Result<()>,
+    G: Send + FnOnce() -> Result<(u64, u64)>,
 {
+    let config = config();
+    let (to_csv, to_ch) = (
+        config.sink_enabled(Sink::Csv),
+        config.sink_enabled(Sink::Clickhouse),
+    );
     std::thread::scope(|s| {
-        let task1 = s.spawn(csv);
-        let task2 = s.spawn(ch);
-        match task1.join() {
-            Ok(res) => _ = res?,
-            Err(err) => bail!("save_csv 运行失败：{err:?}"),
+        let task1 = to_csv.then(|| s.spawn(csv));
+        let task2 = to_ch.then(|| s.spawn(ch));
+        // 线程 panic 时继续向上 panic，而不是转换成错误
+        match task1.map(|t| t.join()) {
+            Some(Ok(res)) => _ = res?,
+            Some(Err(err)) => std::panic::resume_unwind(err),
+            None => debug!("未启用 csv，跳过保存"),
         }
-        match task2.join() {
-            Ok(res) => res?,
-            Err(err) => bail!("保存到 clickhouse 运行失败：{err:?}"),
+        match task2.map(|t| t.join()) {
+            Some(Ok(res)) => {
+                let (rows, deduplicated) = res?;
+                manifest::inserted(rows, deduplicated);
+            }
+            Some(Err(err)) => std::panic::resume_unwind(err),
+            None => debug!("未启用 clickhouse，跳过录入"),
         }
         Ok(())
     })
@@ -0,0 +1,72 @@
+//! 写入 parquet 文件（需要 `parquet` feature）：按列提供数据，列的顺序与 schema 一致。
+use super::{init_data, ByteSize, Context, Error, Result};
+use parquet::{
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, FloatType, Int32Type, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use std::{fs::File, path::PathBuf, sync::Arc};
+use time::Date;
+
+/// 一列数据
+pub enum Column {
+    /// `int32 (DATE)`：距 1970-01-01 的天数
+    Date(Vec<i32>),
+    Bool(Vec<bool>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    /// `binary (UTF8)`
+    Str(Vec<ByteArray>),
+}
+
+impl Column {
+    pub fn date(dates: impl IntoIterator<Item = Date>) -> Column {
+        const UNIX_EPOCH: i32 = 2440588;
+        Column::Date(
+            dates
+                .into_iter()
+                .map(|d| d.to_julian_day() - UNIX_EPOCH)
+                .collect(),
+        )
+    }
+
+    pub fn str<'s>(s: impl IntoIterator<Item = &'s str>) -> Column {
+        Column::Str(s.into_iter().map(ByteArray::from).collect())
+    }
+}
+
+/// 写入缓存目录下的 `{filename}.parquet`，`schema` 为 parquet 的 message type
+pub fn save_parquet(schema: &str, columns: Vec<Column>, filename: &str) -> Result<PathBuf> {
+    let mut path = init_data().cache_dir.join(filename);
+    path.set_extension("parquet");
+    let err = || format!("无法写入 {}", path.display());
+    let schema = Arc::new(
+        parse_message_type(schema).or_err(Error::Export, || format!("schema 无效：{schema}"))?,
+    );
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(&path)?, schema, props)
+        .or_err(Error::Export, err)?;
+    let mut row_group = writer.next_row_group().or_err(Error::Export, err)?;
+    let mut columns = columns.into_iter();
+    while let Some(mut col) = row_group.next_column().or_err(Error::Export, err)? {
+        let data = columns.next().or_err(Error::Export, || {
+            format!("{} 的列数少于 schema", path.display())
+        })?;
+        match data {
+            Column::Date(v) => col.typed::<Int32Type>().write_batch(&v, None, None),
+            Column::Bool(v) => col.typed::<BoolType>().write_batch(&v, None, None),
+            Column::Long(v) => col.typed::<Int64Type>().write_batch(&v, None, None),
+            Column::Float(v) => col.typed::<FloatType>().write_batch(&v, None, None),
+            Column::Double(v) => col.typed::<DoubleType>().write_batch(&v, None, None),
+            Column::Str(v) => col.typed::<ByteArrayType>().write_batch(&v, None, None),
+        }
+        .or_err(Error::Export, err)?;
+        col.close().or_err(Error::Export, err)?;
+    }
+    row_group.close().or_err(Error::Export, err)?;
+    writer.close().or_err(Error::Export, err)?;
+    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    info!("{} 已被写入 ({})", path.display(), ByteSize(size));
+    Ok(path)
+}
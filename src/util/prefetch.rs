@@ -0,0 +1,236 @@
+//! 并发下载：在后台线程中预先下载一批链接，[`fetch`](super::fetch) 按原来的顺序取用，
+//! 因此解析和录入仍然在当前线程中依次进行，csv 文件和 clickhouse 去重的结果与逐个下载时相同。
+//!
+//! * 同时进行的下载不超过 `network.jobs` 个，同一主机不超过 `network.per_host` 个
+//! * 已下载而尚未取用的文件不超过 `2 * jobs` 个，以限制内存占用
+use super::{config, init_data};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    time::Instant,
+};
+
+/// 后台下载的结果
+#[derive(Debug)]
+pub enum Fetched {
+    /// 响应内容和开始下载的时间
+    Body(Vec<u8>, Instant),
+    /// 404
+    NotFound,
+    /// 请求或者读取失败的原因
+    Failed(String),
+}
+
+#[derive(Debug)]
+enum Slot {
+    Queued,
+    Running,
+    Done(Fetched),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    slots: HashMap<String, Slot>,
+    queue: VecDeque<String>,
+    /// 每个主机正在进行的下载数
+    hosts: HashMap<String, usize>,
+    /// 正在下载以及已下载而尚未取用的数量
+    outstanding: usize,
+    window: usize,
+    per_host: usize,
+}
+
+/// 下载一个链接；全局调度使用 GET 请求，也可以在 [`Scheduler::new`] 中替换
+pub type Download = Box<dyn Fn(&str) -> Fetched + Send + Sync>;
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    download: Download,
+}
+
+/// 后台下载的调度：同时进行的下载数、同一主机的下载数以及尚未取用的结果数都有上限
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    jobs: usize,
+}
+
+/// 链接中的主机名
+pub fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+/// 后台下载的范围：被丢弃时取消尚未开始的下载，并释放尚未取用的结果
+#[must_use = "被丢弃时会取消后台下载"]
+pub struct Prefetch {
+    shared: Arc<Shared>,
+    urls: Vec<String>,
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap();
+        for url in &self.urls {
+            // 正在下载的由下载线程在完成时减少 outstanding
+            if let Some(Slot::Done(_)) = state.slots.remove(url) {
+                state.outstanding -= 1;
+            }
+        }
+        let State { queue, slots, .. } = &mut *state;
+        queue.retain(|url| slots.contains_key(url));
+        shared.changed.notify_all();
+    }
+}
+
+/// 开始在后台按顺序下载 `urls`（见 [`Scheduler::start`]），使用 `network.jobs` 和 `network.per_host`
+pub fn start(urls: Vec<String>) -> Prefetch {
+    Scheduler::global().start(urls)
+}
+
+/// 取出 `url` 的后台下载结果（见 [`Scheduler::take`]）
+pub fn take(url: &str) -> Option<Fetched> {
+    Scheduler::global().take(url)
+}
+
+impl Scheduler {
+    /// 最多 `jobs` 个同时进行，同一主机最多 `per_host` 个；`jobs` 不大于 1 时不在后台下载
+    pub fn new(jobs: usize, per_host: usize, download: Download) -> Scheduler {
+        let state = State {
+            window: 2 * jobs,
+            per_host: per_host.max(1),
+            ..State::default()
+        };
+        let shared = Shared {
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+            download,
+        };
+        Scheduler {
+            shared: Arc::new(shared),
+            jobs,
+        }
+    }
+
+    fn global() -> &'static Scheduler {
+        static GLOBAL: OnceLock<Scheduler> = OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            let net = &config().network;
+            Scheduler::new(net.jobs, net.per_host, Box::new(download))
+        })
+    }
+
+    /// 开始在后台按顺序下载 `urls`；`jobs` 不大于 1 时什么也不做
+    pub fn start(&self, urls: Vec<String>) -> Prefetch {
+        let shared = &self.shared;
+        let jobs = self.jobs;
+        if jobs <= 1 || urls.is_empty() {
+            return Prefetch {
+                shared: Arc::clone(shared),
+                urls: Vec::new(),
+            };
+        }
+        let per_host = {
+            let mut state = shared.state.lock().unwrap();
+            for url in &urls {
+                if !state.slots.contains_key(url) {
+                    state.slots.insert(url.clone(), Slot::Queued);
+                    state.queue.push_back(url.clone());
+                }
+            }
+            state.per_host
+        };
+        info!(
+            "并发下载 {} 个文件：最多 {jobs} 个同时进行，同一主机最多 {per_host} 个",
+            urls.len(),
+        );
+        for _ in 0..jobs.min(urls.len()) {
+            let shared = Arc::clone(shared);
+            std::thread::spawn(move || worker(&shared));
+        }
+        Prefetch {
+            shared: Arc::clone(shared),
+            urls,
+        }
+    }
+
+    /// 取出 `url` 的后台下载结果，必要时等待下载完成；不在后台下载的范围内时返回 None。
+    /// 尚未开始的下载不再等待，而是返回 None 由调用方直接下载，避免因取用顺序不同而互相等待。
+    pub fn take(&self, url: &str) -> Option<Fetched> {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            match state.slots.get(url)? {
+                Slot::Queued => {
+                    state.slots.remove(url);
+                    state.queue.retain(|u| u != url);
+                    return None;
+                }
+                Slot::Running => state = shared.changed.wait(state).unwrap(),
+                Slot::Done(_) => {
+                    let Some(Slot::Done(fetched)) = state.slots.remove(url) else {
+                        unreachable!()
+                    };
+                    state.outstanding -= 1;
+                    shared.changed.notify_all();
+                    return Some(fetched);
+                }
+            }
+        }
+    }
+
+    /// 正在下载以及已下载而尚未取用的数量
+    pub fn outstanding(&self) -> usize {
+        self.shared.state.lock().unwrap().outstanding
+    }
+}
+
+/// 依次取出队首的链接下载；队列为空时退出
+fn worker(shared: &Shared) {
+    loop {
+        let url = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                let Some(url) = state.queue.front() else {
+                    return;
+                };
+                let host = host(url).to_owned();
+                let running = state.hosts.get(&host).copied().unwrap_or(0);
+                if state.outstanding < state.window && running < state.per_host {
+                    let url = state.queue.pop_front().unwrap();
+                    *state.hosts.entry(host).or_default() += 1;
+                    state.outstanding += 1;
+                    state.slots.insert(url.clone(), Slot::Running);
+                    break url;
+                }
+                state = shared.changed.wait(state).unwrap();
+            }
+        };
+        debug!("后台下载 {url}");
+        let fetched = (shared.download)(&url);
+        let mut state = shared.state.lock().unwrap();
+        if let Some(n) = state.hosts.get_mut(host(&url)) {
+            *n -= 1;
+        }
+        if matches!(state.slots.get(&url), Some(Slot::Running)) {
+            state.slots.insert(url, Slot::Done(fetched));
+        } else {
+            // 已被取消
+            state.outstanding -= 1;
+        }
+        shared.changed.notify_all();
+    }
+}
+
+fn download(url: &str) -> Fetched {
+    let started = Instant::now();
+    match init_data().agent.get(url).call() {
+        Ok(resp) => match super::progress::read_body(url, resp) {
+            Ok(buf) => Fetched::Body(buf, started),
+            Err(err) => Fetched::Failed(format!("{err:?}")),
+        },
+        Err(ureq::Error::Status(404, _)) => Fetched::NotFound,
+        Err(err) => Fetched::Failed(format!("{err:?}")),
+    }
+}
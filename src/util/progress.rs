@@ -0,0 +1,104 @@
+//! 进度条：每个下载文件的字节数和速度，以及多个年份、品种的总体进度。
+//!
+//! 标准错误不是终端时不显示（见 [`indicatif::ProgressDrawTarget::stderr`]），以 JSON 输出事件时也不显示。
+//! 终端日志由 [`Suspend`] 在输出时暂时清除进度条，避免两者互相覆盖。
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{io::Read, sync::OnceLock};
+
+fn multi() -> &'static MultiProgress {
+    static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI.get_or_init(|| {
+        if super::event::json() {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        } else {
+            MultiProgress::new()
+        }
+    })
+}
+
+/// 链接中的文件名
+pub fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').find(|s| !s.is_empty()).unwrap_or(path)
+}
+
+/// 读取响应内容，同时显示下载的字节数和速度；读取完后清除进度条
+pub fn read_body(url: &str, resp: ureq::Response) -> std::io::Result<Vec<u8>> {
+    let len = resp
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+    let bar = match len {
+        Some(len) => ProgressBar::new(len).with_style(style(
+            "{spinner} {msg} {bytes}/{total_bytes} {bytes_per_sec} {wide_bar} {eta}",
+        )),
+        None => {
+            ProgressBar::new_spinner().with_style(style("{spinner} {msg} {bytes} {bytes_per_sec}"))
+        }
+    };
+    let bar = multi().add(bar.with_message(file_name(url).to_owned()));
+    let mut buf = Vec::with_capacity(len.map_or(1024 * 1024 * 4, |len| len as usize));
+    let res = bar.wrap_read(resp.into_reader()).read_to_end(&mut buf);
+    bar.finish_and_clear();
+    res.map(|_| buf)
+}
+
+/// 输出日志时暂时清除进度条，输出后重新绘制（见 [`MultiProgress::suspend`]）
+pub(crate) struct Suspend(pub(crate) Box<dyn simplelog::SharedLogger>);
+
+impl log::Log for Suspend {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.0.enabled(record.metadata()) {
+            multi().suspend(|| self.0.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+impl simplelog::SharedLogger for Suspend {
+    fn level(&self) -> log::LevelFilter {
+        self.0.level()
+    }
+
+    fn config(&self) -> Option<&simplelog::Config> {
+        self.0.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn log::Log> {
+        Box::new(*self)
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template).unwrap_or_else(|_| ProgressStyle::default_bar())
+}
+
+/// 总体进度：已处理的文件（年份、品种或者交易日）数；被丢弃时清除
+pub struct Overall(ProgressBar);
+
+/// 开始显示总体进度，共 `len` 项
+pub fn overall(len: usize, msg: &str) -> Overall {
+    let bar = ProgressBar::new(len as u64)
+        .with_style(style("{msg} [{pos}/{len}] {wide_bar} {elapsed_precise}"))
+        .with_message(msg.to_owned());
+    Overall(multi().add(bar))
+}
+
+impl Overall {
+    /// 完成一项
+    pub fn inc(&self) {
+        self.0.inc(1);
+    }
+}
+
+impl Drop for Overall {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+    }
+}
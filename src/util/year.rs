@@ -0,0 +1,131 @@
+//! 命令行的年份参数：由逗号分隔的多段组成，根据交易所支持的年份范围展开成具体年份。
+use crate::{Error, Result};
+use regex::Regex;
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
+/// 年份：由逗号分隔的多段组成，每段可以是
+/// * `2022`：单独一年
+/// * `2020..2023`：不包含 2023 年
+/// * `2020..=2023`：包含 2023 年
+/// * `2018..`：从 2018 年至支持的最后一年
+/// * `..2015`：从交易所最早提供数据的年份至 2014 年（`..=2015` 则包含 2015 年）
+#[derive(Debug, PartialEq, Eq)]
+pub struct Year(pub Vec<YearSpan>);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum YearSpan {
+    Single(u16),
+    /// start..end 或者 start..=end；None 表示该端是开放的
+    Range {
+        start: Option<u16>,
+        end: Option<u16>,
+        inclusive: bool,
+    },
+}
+
+impl std::fmt::Display for YearSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            YearSpan::Single(year) => write!(f, "{year}"),
+            YearSpan::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                f.write_str(if inclusive { "..=" } else { ".." })?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Year {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern =
+            r"^((?P<range>(?P<start>\d{4})?\.\.(?P<eq>=)?(?P<end>\d{4})?)|(?P<single>\d{4}))$";
+        let re = Regex::new(pattern).unwrap();
+        let spans = s
+            .split(',')
+            .map(|span| {
+                let span = span.trim();
+                let cap = re.captures(span).ok_or_else(|| {
+                    format!(
+                        r"{span} 不是年份，应输入 \d{{4}}、\d{{4}}..\d{{4}}、\d{{4}}..=\d{{4}}、\d{{4}}.. 或者 ..\d{{4}}，并用逗号分隔"
+                    )
+                })?;
+                let parse = |key: &str| {
+                    cap.name(key)
+                        .map(|m| m.as_str().parse::<u16>())
+                        .transpose()
+                        .map_err(|err| format!("{span} 无法解析为 u16: {err}"))
+                };
+                if cap.name("range").is_some() {
+                    let inclusive = cap.name("eq").is_some();
+                    let (start, end) = (parse("start")?, parse("end")?);
+                    if inclusive && end.is_none() {
+                        return Err(format!("{span} 缺少结束年份"));
+                    }
+                    // 开放的一端由交易所的年份范围确定，这里只检查两端都给出的情况
+                    let empty = match (start.unwrap_or(0), end) {
+                        (start, Some(end)) if inclusive => start > end,
+                        (start, Some(end)) => start >= end,
+                        _ => false,
+                    };
+                    if empty {
+                        return Err(format!("{span} 不包含任何年份"));
+                    }
+                    Ok(YearSpan::Range {
+                        start,
+                        end,
+                        inclusive,
+                    })
+                } else {
+                    Ok(YearSpan::Single(parse("single")?.unwrap()))
+                }
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Year(spans))
+    }
+}
+
+impl Year {
+    /// 在 `span`（如 [`Exchange::years`](crate::Exchange::years)）内展开成具体年份（升序、去重）：
+    /// 开放的一端取 `span` 的端点，超出 `span` 的年份返回 [`Error::UnsupportedYear`]，因此在联网之前校验年份。
+    pub fn resolve(&self, span: RangeInclusive<u16>) -> Result<Vec<u16>> {
+        let (min, max) = (*span.start(), *span.end());
+        let mut years = BTreeSet::new();
+        for y in &self.0 {
+            let (start, end) = match *y {
+                YearSpan::Single(year) => (year, year),
+                YearSpan::Range {
+                    start,
+                    end,
+                    inclusive,
+                } => {
+                    let end = match end {
+                        Some(end) if inclusive => end,
+                        // 解析时已保证 end 大于 0
+                        Some(end) => end - 1,
+                        None => max,
+                    };
+                    (start.unwrap_or(min), end)
+                }
+            };
+            for year in [start, end] {
+                if !span.contains(&year) {
+                    return Err(Error::UnsupportedYear { year, min, max });
+                }
+            }
+            years.extend(start..=end);
+        }
+        Ok(years.into_iter().collect())
+    }
+}
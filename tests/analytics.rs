@@ -0,0 +1,277 @@
+use commodity_exchange_zh::{
+    analytics::{
+        continuous::{continuous, Adjust, Direction, Method},
+        curve::{calendar_spreads, curve, Shape},
+        date_condition, delete_sql,
+        index::{index, Weight},
+        main_contract::{main_contract, Options, Rank},
+        resample::{resample, Period},
+    },
+    ce::{Bar, Selection},
+    util, Exchange,
+};
+use time::{macros::date, Date};
+
+fn bar(date: Date, code: &str, close: f32, position: u32) -> Bar {
+    Bar {
+        date,
+        code: code.into(),
+        open: close,
+        high: close,
+        low: close,
+        close,
+        settle: close,
+        vol: position / 2,
+        amount: 0.0,
+        position,
+        ce: Exchange::czce,
+        prev_close: None,
+        dsp: None,
+    }
+}
+
+/// MA401 的持仓逐渐被 MA405 超过，之后 MA401 到期
+fn ma() -> Vec<Bar> {
+    let d = [
+        date!(2023 - 12 - 04),
+        date!(2023 - 12 - 05),
+        date!(2023 - 12 - 06),
+        date!(2023 - 12 - 07),
+        date!(2023 - 12 - 08),
+    ];
+    vec![
+        bar(d[0], "MA401", 2500.0, 1000),
+        bar(d[0], "MA405", 2550.0, 600),
+        bar(d[1], "MA401", 2510.0, 900),
+        bar(d[1], "MA405", 2560.0, 950),
+        bar(d[2], "MA401", 2520.0, 800),
+        bar(d[2], "MA405", 2570.0, 1000),
+        bar(d[3], "MA401", 2530.0, 500),
+        bar(d[3], "MA405", 2580.0, 1200),
+        bar(d[4], "MA405", 2590.0, 1300),
+        bar(d[4], "MA409", 2600.0, 100),
+    ]
+}
+
+#[test]
+fn main_contract_rolls_next_day() {
+    let series = main_contract(&ma(), &Options::default());
+    let codes: Vec<_> = series.bars.iter().map(|b| b.code.as_str()).collect();
+    // 12-05 收盘后 MA405 领先，12-06 起换月
+    assert_eq!(codes, ["MA401", "MA401", "MA405", "MA405", "MA405"]);
+    assert_eq!(series.rolls.len(), 1);
+    assert_eq!(series.rolls[0].date, date!(2023 - 12 - 06));
+    assert_eq!(
+        (&*series.rolls[0].from, &*series.rolls[0].to),
+        ("MA401", "MA405")
+    );
+    assert!(series.bars[2].roll && !series.bars[3].roll);
+    assert_eq!(series.bars[0].product, "MA");
+}
+
+#[test]
+fn main_contract_hysteresis() {
+    // 需要多出 10% 且连续 2 天：12-06、12-07 满足，12-08 生效，此时 MA401 已到期
+    let opts = Options {
+        rank: Rank::Oi,
+        threshold: 0.1,
+        confirm_days: 2,
+    };
+    let series = main_contract(&ma(), &opts);
+    let codes: Vec<_> = series.bars.iter().map(|b| b.code.as_str()).collect();
+    assert_eq!(codes, ["MA401", "MA401", "MA401", "MA401", "MA405"]);
+    assert_eq!(series.rolls[0].date, date!(2023 - 12 - 08));
+
+    // 按成交量时结果相同，因为测试数据中成交量与持仓量成比例
+    let by_vol = main_contract(
+        &ma(),
+        &Options {
+            rank: Rank::Vol,
+            ..opts
+        },
+    );
+    assert_eq!(by_vol, series);
+}
+
+#[test]
+fn continuous_adjustment() {
+    let bars = ma();
+    let main = main_contract(&bars, &Options::default());
+    // 12-06 换月：前一天 MA401 收盘 2510，MA405 收盘 2560
+    let closes = |method, direction| {
+        continuous(&bars, &main, Adjust { method, direction })
+            .into_iter()
+            .map(|b| b.close)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        closes(Method::Diff, Direction::Backward),
+        [2550.0, 2560.0, 2570.0, 2580.0, 2590.0]
+    );
+    assert_eq!(
+        closes(Method::Diff, Direction::Forward),
+        [2500.0, 2510.0, 2520.0, 2530.0, 2540.0]
+    );
+    let ratio = continuous(&bars, &main, Adjust::default());
+    let factor = 2560.0 / 2510.0;
+    assert_eq!(ratio[0].factor, factor);
+    assert_eq!(ratio[0].close, (2500.0 * factor) as f32);
+    assert_eq!((ratio[4].factor, ratio[4].close), (1.0, 2590.0));
+    let forward = closes(Method::Ratio, Direction::Forward);
+    assert_eq!(forward[1], 2510.0);
+    assert_eq!(forward[2], (2570.0 / factor) as f32);
+}
+
+#[test]
+fn product_index() {
+    let bars = ma();
+    let prev_oi = index(&bars, Weight::PrevOi);
+    // 第一天没有前一交易日的持仓量
+    assert_eq!(prev_oi.len(), 4);
+    assert_eq!(prev_oi[0].date, date!(2023 - 12 - 05));
+    assert_eq!(prev_oi[0].close, 2528.75);
+    assert_eq!((prev_oi[0].position, prev_oi[0].contracts), (1850, 2));
+    // MA409 新上市，当日权重为 0；MA401 已到期
+    assert_eq!((prev_oi[3].close, prev_oi[3].contracts), (2590.0, 1));
+    assert_eq!(prev_oi[3].position, 1400);
+
+    let vol = index(&bars, Weight::Vol);
+    assert_eq!(vol.len(), 5);
+    assert_eq!(vol[0].close, 2518.75);
+    assert_eq!(vol[0].vol, 800);
+    assert_eq!(vol[0].product, "MA");
+}
+
+#[test]
+fn resample_by_week_and_month() {
+    let mut bars = vec![
+        bar(date!(2023 - 12 - 25), "MA405", 2550.0, 600),
+        bar(date!(2023 - 12 - 27), "MA405", 2580.0, 700),
+        bar(date!(2023 - 12 - 29), "MA405", 2560.0, 800),
+        // 2024-01-01 为节假日；ISO 周与 2023-12-29 不同
+        bar(date!(2024 - 01 - 02), "MA405", 2600.0, 900),
+    ];
+    // 无成交的开盘价、最低价为 0，不参与合成
+    bars[0].open = 0.0;
+    bars[0].low = 0.0;
+    bars[1].high = 2590.0;
+    bars[1].low = 2540.0;
+
+    let weeks = resample(&bars, Period::W);
+    assert_eq!(weeks.len(), 2);
+    let w = &weeks[0];
+    assert_eq!(
+        (w.start, w.end, w.days),
+        (date!(2023 - 12 - 25), date!(2023 - 12 - 29), 3)
+    );
+    assert_eq!(
+        (w.open, w.high, w.low, w.close),
+        (2580.0, 2590.0, 2540.0, 2560.0)
+    );
+    assert_eq!((w.vol, w.position), (1050, 800));
+    assert_eq!((weeks[1].start, weeks[1].days), (date!(2024 - 01 - 02), 1));
+
+    let months = resample(&bars, Period::M);
+    assert_eq!(months.len(), 2);
+    assert_eq!(months[0].end, date!(2023 - 12 - 29));
+    assert_eq!(months[1].close, 2600.0);
+}
+
+#[test]
+fn resample_continuous() {
+    let bars = ma();
+    let main = main_contract(&bars, &Options::default());
+    let adjust = Adjust {
+        method: Method::Diff,
+        direction: Direction::Backward,
+    };
+    // 价差复权后的价格可以为 0 或者负数，仍然参与合成
+    let shifted = |by: f32| {
+        let mut data = continuous(&bars, &main, adjust);
+        for b in &mut data {
+            for p in [
+                &mut b.open,
+                &mut b.high,
+                &mut b.low,
+                &mut b.close,
+                &mut b.settle,
+            ] {
+                *p -= by;
+            }
+        }
+        resample(&data, Period::W)
+    };
+    let w = &shifted(2600.0)[0];
+    assert_eq!(
+        (w.open, w.high, w.low, w.close),
+        (-50.0, -10.0, -50.0, -10.0)
+    );
+    let w = &shifted(2550.0)[0];
+    assert_eq!((w.open, w.high, w.low, w.close), (0.0, 40.0, 0.0, 40.0));
+    // 成交量、持仓量来自主力合约
+    let vol: u64 = main.bars.iter().map(|b| b.vol as u64).sum();
+    assert_eq!((w.vol, w.position), (vol, 1300));
+}
+
+#[test]
+fn delivery_month() {
+    let d = date!(2023 - 10 - 19);
+    assert_eq!(util::delivery("MA401", d), Some((2024, 1)));
+    assert_eq!(util::delivery("MA311", d), Some((2023, 11)));
+    assert_eq!(util::delivery("V2401", d), Some((2024, 1)));
+    // 郑州交易所 2019 年交易的 MA001 为 2020 年 1 月
+    assert_eq!(
+        util::delivery("MA001", date!(2019 - 06 - 03)),
+        Some((2020, 1))
+    );
+    assert_eq!(util::delivery("MA413", d), None);
+    assert_eq!(util::delivery("MA", d), None);
+}
+
+#[test]
+fn term_structure() {
+    let bars = ma();
+    let c = curve(&bars, "ma", date!(2023 - 12 - 08)).unwrap();
+    let codes: Vec<_> = c.points.iter().map(|p| p.code.as_str()).collect();
+    assert_eq!(codes, ["MA405", "MA409"]);
+    assert_eq!(c.points[1].delivery, (2024, 9));
+    assert_eq!(c.points[1].spread, 10.0);
+    assert_eq!(c.shape, Shape::Contango);
+    assert!(curve(&bars, "MA", date!(2023 - 12 - 09)).is_none());
+
+    let spreads = calendar_spreads(&bars, "MA");
+    assert_eq!(spreads.len(), 5);
+    assert_eq!(
+        (spreads[0].near.as_str(), spreads[0].far.as_str()),
+        ("MA401", "MA405")
+    );
+    assert_eq!(spreads[0].spread, 50.0);
+    assert_eq!(spreads[4].near, "MA405");
+}
+
+/// 只重新生成部分日期时，只删除该范围内的旧数据
+#[test]
+fn delete_within_dates() {
+    let all = Selection::default();
+    assert_eq!(
+        delete_sql("qihuo.ce_main", &["MA", "V"], &date_condition(&all)),
+        "ALTER TABLE qihuo.ce_main DELETE WHERE product IN ('MA', 'V') SETTINGS mutations_sync = 1"
+    );
+    let from = Selection {
+        from: Some(date!(2023 - 01 - 01)),
+        ..Selection::default()
+    };
+    assert_eq!(
+        delete_sql("qihuo.ce_main", &["MA"], &date_condition(&from)),
+        "ALTER TABLE qihuo.ce_main DELETE WHERE product IN ('MA') AND date >= '2023-01-01' \
+         SETTINGS mutations_sync = 1"
+    );
+    let range = Selection {
+        to: Some(date!(2023 - 06 - 30)),
+        ..from
+    };
+    assert_eq!(
+        date_condition(&range),
+        " AND date >= '2023-01-01' AND date <= '2023-06-30'"
+    );
+}
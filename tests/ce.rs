@@ -0,0 +1,141 @@
+use commodity_exchange_zh::{
+    ce::{self, Bar},
+    czce, dce, Exchange,
+};
+use time::macros::date;
+
+fn czce_data(date: time::Date) -> czce::Data {
+    czce::Data {
+        date,
+        code: "ma401".into(),
+        prev: 2400.0,
+        open: 2410.0,
+        high: 2430.0,
+        low: 2390.0,
+        close: 2420.0,
+        settle: 2415.0,
+        zd1: 20.0,
+        zd2: 15.0,
+        vol: 1001,
+        position: 3001,
+        pos_delta: -10,
+        amount: 2000.5,
+        dsp: None,
+    }
+}
+
+#[test]
+fn czce_double_sided_before_2020() {
+    let bar = Bar::from(czce_data(date!(2019 - 12 - 31)));
+    assert_eq!(bar.code, "MA401");
+    assert_eq!(bar.ce, Exchange::czce);
+    assert_eq!((bar.vol, bar.amount, bar.position), (500, 1000.25, 1500));
+    assert_eq!((bar.open, bar.high, bar.low), (2410.0, 2430.0, 2390.0));
+    assert_eq!((bar.close, bar.settle), (2420.0, 2415.0));
+}
+
+#[test]
+fn czce_single_sided_since_2020() {
+    let bar = Bar::from(czce_data(date!(2020 - 01 - 02)));
+    assert_eq!((bar.vol, bar.amount, bar.position), (1001, 2000.5, 3001));
+    assert_eq!(bar.prev_close, None);
+    assert_eq!(bar.dsp, None);
+}
+
+#[test]
+fn dce_double_sided_and_yuan() {
+    let data = dce::Data {
+        code: "v2201".into(),
+        date: date!(2022 - 01 - 04),
+        prev: 8292.0,
+        open: 8293.0,
+        high: 8578.0,
+        low: 8293.0,
+        close: 8550.0,
+        settle: 8462.0,
+        zd1: 258.0,
+        zd2: 170.0,
+        vol: 1914,
+        amount: 80987940,
+        position: 26364,
+        prev_close: Some(8300.0),
+    };
+    let bar = Bar::from(data);
+    assert_eq!(bar.code, "V2201");
+    assert_eq!(bar.ce, Exchange::dce);
+    assert_eq!((bar.vol, bar.amount, bar.position), (957, 8098.794, 13182));
+    assert_eq!(bar.prev_close, Some(8300.0));
+}
+
+#[test]
+fn selection_by_contract() {
+    let selection = ce::Selection {
+        products: vec!["ma".into()],
+        contracts: vec!["ma401".into()],
+        from: Some(date!(2023 - 01 - 01)),
+        to: None,
+    };
+    assert_eq!(
+        selection.where_clause().unwrap(),
+        "1 AND extract(code, '^[A-Z]+') IN ('MA') AND code IN ('MA401') AND date >= '2023-01-01'"
+    );
+    let bar = Bar::from(czce_data(date!(2023 - 10 - 19)));
+    assert!(selection.matches(&bar));
+    assert!(!selection.matches(&Bar::from(czce_data(date!(2022 - 12 - 30)))));
+    let other = ce::Selection {
+        contracts: vec!["MA405".into()],
+        ..Default::default()
+    };
+    assert!(!other.matches(&bar));
+    let invalid = ce::Selection {
+        contracts: vec!["MA401'".into()],
+        ..Default::default()
+    };
+    assert!(invalid.where_clause().is_err());
+}
+
+/// 缓存的 csv 文件可以被读回（`ce::load_cache`）
+#[test]
+fn czce_csv_roundtrip() {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.serialize(czce_data(date!(2023 - 10 - 19))).unwrap();
+    let bytes = writer.into_inner().unwrap();
+    let data: czce::Data = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(&bytes[..])
+        .deserialize()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(Bar::from(data), Bar::from(czce_data(date!(2023 - 10 - 19))));
+}
+
+/// 早期版本保存的文件（`|` 分隔、带表头）被跳过，不影响其他文件
+#[test]
+fn load_cache_skips_unreadable_files() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("ce-test-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.serialize(czce_data(date!(2023 - 10 - 19)))?;
+    std::fs::write(dir.join("czce-ALLFUTURES2023.csv"), writer.into_inner()?)?;
+    std::fs::write(
+        dir.join("czce-ALLFUTURES2022.csv"),
+        "交易日期|合约代码|昨结算|今开盘\n2022-01-04|MA205|2500|2510\n",
+    )?;
+    std::fs::write(dir.join("dce-2022-豆一.csv"), "not,a,bar\n")?;
+    std::fs::write(dir.join("manifest.jsonl"), "{}\n")?;
+
+    let bars = ce::load_cache_dir(&dir, &Default::default());
+    std::fs::remove_dir_all(&dir)?;
+    let bars = bars?;
+    assert_eq!(bars.len(), 1);
+    assert_eq!(
+        (bars[0].code.as_str(), bars[0].date),
+        ("MA401", date!(2023 - 10 - 19))
+    );
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use color_eyre::eyre::Result;
+use commodity_exchange_zh::{
+    util::{Config, Sink},
+    Exchange,
+};
+
+#[test]
+fn config_file_and_env() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("ce-test-config-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+database = "staging"
+sinks = ["csv"]
+
+[clickhouse]
+host = "10.0.0.1"
+port = 9440
+
+[network]
+proxy = "http://127.0.0.1:7890"
+"#,
+    )?;
+    let mut config = Config::from_file(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(config.database, "staging");
+    assert_eq!(config.sinks, [Sink::Csv]);
+    assert!(!config.sink_enabled(Sink::Clickhouse));
+    // 未设置的项使用默认值
+    assert_eq!(config.cache_dir, Config::default().cache_dir);
+    assert_eq!(config.exchanges, [Exchange::czce, Exchange::dce]);
+    assert_eq!(config.network.timeout, 300);
+    assert_eq!((config.network.jobs, config.network.per_host), (1, 2));
+    assert_eq!(
+        config.clickhouse.args(),
+        ["--host", "10.0.0.1", "--port", "9440"]
+    );
+    config.network.agent()?;
+
+    std::env::set_var("CE_DATABASE", "production");
+    std::env::set_var("CE_TABLE_PREFIX", "test_");
+    std::env::set_var("CE_SINKS", "csv,clickhouse");
+    std::env::set_var("CE_CLICKHOUSE_PORT", "9000");
+    std::env::set_var("CE_JOBS", "4");
+    config.apply_env()?;
+    assert_eq!(config.network.jobs, 4);
+    assert_eq!(config.database, "production");
+    assert_eq!(config.table("czce"), "production.test_czce");
+    assert_eq!(
+        config.render(
+            "CREATE DATABASE IF NOT EXISTS {database}; SELECT * FROM {database}.{prefix}ce;"
+        ),
+        "CREATE DATABASE IF NOT EXISTS production; SELECT * FROM production.test_ce;"
+    );
+    assert_eq!(config.sinks, [Sink::Csv, Sink::Clickhouse]);
+    assert_eq!(config.clickhouse.port, Some(9000));
+    // 密码只通过环境变量传给客户端
+    std::env::set_var("CE_CLICKHOUSE_PASSWORD", "secret");
+    config.apply_env()?;
+    assert!(!config.clickhouse.args().contains(&"secret".to_owned()));
+    assert_eq!(
+        config.clickhouse.envs(),
+        [("CLICKHOUSE_PASSWORD", "secret".to_owned())]
+    );
+
+    std::env::set_var("CE_CLICKHOUSE_PORT", "port");
+    assert!(config.apply_env().is_err());
+    Ok(())
+}
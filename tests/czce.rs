@@ -0,0 +1,103 @@
+use color_eyre::eyre::Result;
+use commodity_exchange_zh::{czce, util};
+
+const TXT: &str = "\
+郑州商品交易所期货历史交易数据
+自2020年1月1日起，成交量、持仓量、成交额、行权量均为单边计算
+交易日期|合约代码|昨结算|今开盘|最高价|最低价|今收盘|今结算|涨跌1|涨跌2|成交量(手)|持仓量|增减量|成交额(万元)|交割结算价
+2023-01-03      |AP303           |8,284.00        |8,305.00        |8,586.00        |8,300.00        |8,586.00        |8,486.00        |302.00          |202.00          |8,129           |29,539          |-2,084          |68,985.41       |                
+2023-01-03      |AP304           |8,058.00        |8,058.00        |8,484.00        |8,058.00        |8,484.00        |8,376.00        |426.00          |318.00          |2,342           |21,181          |-859            |19,616.25       |                
+";
+
+#[test]
+fn rows() -> Result<()> {
+    util::init_test_log();
+    let rows = czce::rows(TXT.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(rows.len(), 2);
+    let ap303 = &rows[0];
+    assert_eq!(ap303.code, "AP303");
+    assert_eq!(ap303.date.to_string(), "2023-01-03");
+    assert_eq!(ap303.prev, 8284.0);
+    assert_eq!(ap303.position, 29539);
+    assert_eq!(ap303.pos_delta, -2084);
+    assert_eq!(ap303.amount, 68985.41);
+    assert_eq!(ap303.dsp, None);
+    assert_eq!(rows[1].code, "AP304");
+
+    // 与 parse_txt 的结果一致
+    let mut v = Vec::new();
+    czce::parse_txt(TXT, Some(|data| v.push(data)))?;
+    assert_eq!(format!("{v:?}"), format!("{rows:?}"));
+    Ok(())
+}
+
+#[test]
+fn rows_with_bad_line() {
+    util::init_test_log();
+    let txt = TXT.replace("8,129", "oops");
+    let rows: Vec<_> = czce::rows(txt.as_bytes()).collect();
+    assert_eq!(rows.len(), 2);
+    assert!(matches!(rows[0], Err(commodity_exchange_zh::Error::Row(_))));
+    assert!(rows[1].is_ok());
+}
+
+#[test]
+fn filter() -> Result<()> {
+    util::init_test_log();
+    let rows = czce::rows(TXT.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+    let date = |s| time::Date::parse(s, time::macros::format_description!("[year]-[month]-[day]"));
+
+    let all = czce::Filter::default();
+    assert!(rows.iter().all(|d| all.matches(d)));
+
+    let products = czce::Filter {
+        products: vec!["ap".into()],
+        ..Default::default()
+    };
+    assert!(rows.iter().all(|d| products.matches(d)));
+    let products = czce::Filter {
+        products: vec!["MA".into(), "TA".into()],
+        ..Default::default()
+    };
+    assert!(!rows.iter().any(|d| products.matches(d)));
+
+    let window = czce::Filter {
+        from: Some(date("2023-01-03")?),
+        to: Some(date("2023-01-03")?),
+        ..Default::default()
+    };
+    assert!(rows.iter().all(|d| window.matches(d)));
+    let window = czce::Filter {
+        from: Some(date("2023-01-04")?),
+        ..Default::default()
+    };
+    assert!(!rows.iter().any(|d| window.matches(d)));
+    assert!(!window.contains_year(2022));
+    assert!(window.contains_year(2024));
+    Ok(())
+}
+
+#[test]
+fn daily_rows() -> Result<()> {
+    util::init_test_log();
+    let txt = "\
+郑州商品交易所期货每日行情表(2023-10-19)
+合约代码|昨结算|今开盘|最高价|最低价|今收盘|今结算|涨跌1|涨跌2|成交量(手)|持仓量|增减量|成交额(万元)|交割结算价
+AP311           |8,771.00        |8,790.00        |8,845.00        |8,701.00        |8,725.00        |8,768.00        |-46.00          |-3.00           |11,520          |39,013          |-2,043          |101,010.00      |                
+AP312           |8,755.00        |8,760.00        |8,820.00        |8,700.00        |8,710.00        |8,751.00        |-45.00          |-4.00           |1,002           |5,001           |10              |8,769.00        |                
+小计            |                |                |                |                |                |                |                |                |12,522          |44,014          |-2,033          |109,779.00      |                
+总计            |                |                |                |                |                |                |                |                |12,522          |44,014          |-2,033          |109,779.00      |                
+";
+    let date = time::macros::date!(2023 - 10 - 19);
+    let rows = czce::daily_rows(txt, date).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(rows.len(), 2);
+    assert!(rows.iter().all(|d| d.date == date));
+    assert_eq!(rows[0].code, "AP311");
+    assert_eq!(
+        (rows[0].vol, rows[0].position, rows[0].amount),
+        (11520, 39013, 101010.0)
+    );
+    assert_eq!(rows[1].pos_delta, 10);
+    assert!(czce::get_daily_url(date).ends_with("/Future/2023/20231019/FutureDataDaily.txt"));
+    Ok(())
+}
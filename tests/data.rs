@@ -1,8 +1,9 @@
 use calamine::Reader;
+use color_eyre::eyre::{ensure, Result};
 use commodity_exchange_zh::{
     czce::parse_txt,
     dce::{parse_download_links, read_xlsx, DownloadLinks, DOWNLOAD_LINKS},
-    ensure, util, Result,
+    util,
 };
 use insta::assert_display_snapshot as shot;
 use regex::Regex;
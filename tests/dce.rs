@@ -0,0 +1,123 @@
+use color_eyre::eyre::Result;
+use commodity_exchange_zh::dce::{parse_download_links, DownloadLinks, Key};
+
+#[test]
+fn links_cache_and_diff() -> Result<()> {
+    let html = include_str!("dce.html");
+    let parsed = parse_download_links(html)?;
+    let embedded = DownloadLinks::new_static()?;
+    let (added, removed) = embedded.diff(&parsed);
+    assert!(added.is_empty() && removed.is_empty());
+
+    let dir = std::env::temp_dir().join(format!("ce-test-links-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    assert_eq!(DownloadLinks::new_cached(&dir)?, None);
+    parsed.save(&dir)?;
+    assert_eq!(DownloadLinks::new_cached(&dir)?.as_ref(), Some(&embedded));
+    std::fs::remove_dir_all(&dir)?;
+
+    // 把 2006 年改成 2005 年后比较
+    let html_2005 = html.replacen(r#"<option value="2006">"#, r#"<option value="2005">"#, 1);
+    let renamed = parse_download_links(&html_2005)?;
+    let (added, removed) = embedded.diff(&renamed);
+    assert!(!added.is_empty());
+    assert_eq!(added.len(), removed.len());
+    assert!(added.iter().all(|Key { year, .. }| *year == 2005));
+    assert!(removed.iter().all(|Key { year, .. }| *year == 2006));
+    Ok(())
+}
+
+#[test]
+fn daily_quotes() -> Result<()> {
+    commodity_exchange_zh::util::init_test_log();
+    let txt = "\
+大连商品交易所 日行情表
+商品名称 交割月份 开盘价 最高价 最低价 收盘价 前结算价 结算价 涨跌 涨跌1 成交量 持仓量 持仓量变化 成交额
+豆一 2407 4,593 4,615 4,580 4,601 4,590 4,598 11 8 60,520 151,308 -2,103 278,270.86
+豆一 2409 - - - 4,620 4,620 4,620 0 0 0 12 0 0
+豆一小计 60,520 151,320 -2,103 278,270.86
+聚氯乙烯 2409 6,110 6,136 6,088 6,118 6,112 6,113 6 1 402,515 742,104 1,535 1,230,463.41
+新品种 2409 100 100 100 100 100 100 0 0 1 1 1 0.01
+总计 463,035 893,424 -568 1,508,734.27
+";
+    let date = time::macros::date!(2024 - 05 - 10);
+    // 未知品种的行被跳过，不影响其他行
+    let data = commodity_exchange_zh::dce::parse_daily(txt, date)?;
+    assert_eq!(data.len(), 3);
+    let a = &data[0];
+    assert_eq!((a.code.as_str(), a.date), ("a2407", date));
+    assert_eq!(
+        (a.prev, a.open, a.close, a.settle),
+        (4590.0, 4593.0, 4601.0, 4598.0)
+    );
+    assert_eq!((a.vol, a.position), (60520, 151308));
+    // 万元 -> 元
+    assert_eq!(a.amount, 2_782_708_600);
+    assert_eq!((data[1].open, data[1].vol), (0.0, 0));
+    assert_eq!(data[2].code, "v2409");
+
+    // 商品名称不在第一列时按表头的位置读取
+    let txt = "\
+交割月份 商品名称 开盘价 最高价 最低价 收盘价 前结算价 结算价 涨跌 涨跌1 成交量 持仓量 成交额
+2407 豆一 4,593 4,615 4,580 4,601 4,590 4,598 11 8 60,520 151,308 278,270.86
+";
+    let data = commodity_exchange_zh::dce::parse_daily(txt, date)?;
+    assert_eq!(data.len(), 1);
+    assert_eq!((data[0].code.as_str(), data[0].open), ("a2407", 4593.0));
+    Ok(())
+}
+
+#[test]
+fn delivery_settlement_prices() -> Result<()> {
+    commodity_exchange_zh::util::init_test_log();
+    let html = r#"
+<table class="dataArea">
+  <tr><th>品种</th><th>合约</th><th>交割日期</th><th>交割结算价</th></tr>
+  <tr><td>豆一</td><td>A2401</td><td>20240119</td><td>4,877</td></tr>
+  <tr><td>铁矿石</td><td>i2401</td><td>20240117</td><td>1001.5</td></tr>
+  <tr><td colspan="4">合计</td></tr>
+</table>"#;
+    let data = commodity_exchange_zh::dce::parse_dsp(html)?;
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0].code, "a2401");
+    assert_eq!(data[0].date, time::macros::date!(2024 - 01 - 19));
+    assert_eq!(data[0].dsp, 4877.0);
+    assert_eq!(data[1].dsp, 1001.5);
+    assert!(commodity_exchange_zh::dce::parse_dsp("<table></table>").is_err());
+    Ok(())
+}
+
+#[test]
+fn xlsx_header_layouts() -> Result<()> {
+    use calamine::DataType;
+    use commodity_exchange_zh::{dce::parse_xslx_header, Error};
+    let header = |cols: &str| -> Vec<_> {
+        cols.split_whitespace()
+            .map(|c| DataType::String(c.into()))
+            .collect()
+    };
+
+    // 有前收盘价：它的位置附加在末尾
+    let with_prev = "合约 日期 前收盘价 前结算价 开盘价 最高价 最低价 收盘价 结算价 涨跌1 涨跌2 成交量 成交额 持仓量";
+    let expected = [0, 1, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 2];
+    assert_eq!(parse_xslx_header(&header(with_prev))?, expected);
+    for name in ["前收盘", "昨收盘价", "昨收盘"] {
+        let cols = with_prev.replace("前收盘价", name);
+        assert_eq!(parse_xslx_header(&header(&cols))?, expected, "{name}");
+    }
+
+    // 没有前收盘价，多出品种名称，成交额写作成交金额且在持仓量之后
+    let without_prev =
+        "品种名称 合约 日期 前结算价 开盘价 最高价 最低价 收盘价 结算价 涨跌1 涨跌2 成交量 持仓量 成交金额";
+    assert_eq!(
+        parse_xslx_header(&header(without_prev))?,
+        [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 12]
+    );
+
+    let missing = without_prev.replace(" 结算价", "");
+    assert!(matches!(
+        parse_xslx_header(&header(&missing)),
+        Err(Error::Header(_))
+    ));
+    Ok(())
+}
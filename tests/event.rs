@@ -0,0 +1,30 @@
+use commodity_exchange_zh::{
+    util::event::{ErrorInfo, Event, Output},
+    Error,
+};
+
+#[test]
+fn event_json() {
+    assert_eq!("json".parse::<Output>().unwrap(), Output::Json);
+    assert!("yaml".parse::<Output>().is_err());
+
+    let event = Event::FetchFinished {
+        url: "http://www.czce.com.cn/ALLFUTURES2023.zip",
+        status: 200,
+        bytes: 3,
+        elapsed_ms: 10,
+    };
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["event"], "fetch_finished");
+    assert_eq!(json["bytes"], 3);
+
+    let err = Error::Network("超时".into());
+    let event = Event::Error(ErrorInfo {
+        kind: err.kind().into(),
+        message: err.to_string(),
+    });
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["event"], "error");
+    assert_eq!(json["kind"], "Network");
+    assert_eq!(json["message"], "网络请求失败：超时");
+}
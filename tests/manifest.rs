@@ -0,0 +1,72 @@
+use commodity_exchange_zh::{
+    util::{
+        manifest::{self, parse, Entry},
+        set_config, Config, Encoding, Sink,
+    },
+    Error, Result,
+};
+use std::time::Instant;
+
+#[test]
+fn entry_roundtrip() {
+    let mut entry = Entry::new("http://www.czce.com.cn/ALLFUTURES2023.zip", b"abc");
+    assert_eq!(entry.bytes, 3);
+    assert_eq!(
+        entry.sha256,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    entry.encoding = Some(Encoding::GBK);
+    entry.files.push("ALLFUTURES2023.txt".into());
+    entry.parsed = 100;
+    entry.rejected = 2;
+    entry.table = Some("qihuo.czce".into());
+    entry.inserted = Some(98);
+    entry.deduplicated = Some(5);
+    entry.elapsed_ms = 1200;
+
+    let line = serde_json::to_string(&entry).unwrap();
+    assert!(line.contains(r#""encoding":"GBK""#), "{line}");
+    assert!(line.contains("+08:00"), "{line}");
+    let parsed = parse(&format!("{line}\n\n{line}\n")).unwrap();
+    assert_eq!(parsed, [entry.clone(), entry]);
+    assert!(parse("{").is_err());
+
+    // 早期的记录没有录入行数和耗时
+    let old = line.split(r#","inserted""#).next().unwrap().to_owned() + "}";
+    let parsed = parse(&old).unwrap();
+    assert_eq!((parsed[0].inserted, parsed[0].elapsed_ms), (None, 0));
+}
+
+/// 出错时尚未写入的记录被标记错误并写入，不会被计入下一个文件
+#[test]
+fn pending_flushed_on_error() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("ce-test-manifest-{}", std::process::id()));
+    set_config(Config {
+        cache_dir: dir.clone(),
+        sinks: vec![Sink::Csv],
+        ..Config::default()
+    })?;
+    let failed = || -> Result<()> {
+        let _manifest = manifest::guard();
+        manifest::record("http://example.com/1.zip", b"1", Instant::now());
+        manifest::count(10, 0);
+        Err(Error::Row("无法解析".into()))
+    };
+    assert!(failed().is_err());
+
+    let _manifest = manifest::guard();
+    manifest::record("http://example.com/2.zip", b"2", Instant::now());
+    let entries = manifest::finish(Some("qihuo.czce"))?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].url, "http://example.com/2.zip");
+
+    let history = manifest::history();
+    std::fs::remove_dir_all(&dir)?;
+    let history = history?;
+    assert_eq!(history.len(), 2);
+    assert!(history[0].error.is_some());
+    assert_eq!((history[0].parsed, history[0].table.as_deref()), (10, None));
+    assert_eq!(history[1].error, None);
+    assert_eq!(manifest::finished().len(), 2);
+    Ok(())
+}
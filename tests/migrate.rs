@@ -0,0 +1,38 @@
+use color_eyre::eyre::Result;
+use commodity_exchange_zh::util::migrate::{parse_applied, pending, MIGRATIONS};
+
+#[test]
+fn migrations_ordered() {
+    for pair in MIGRATIONS.windows(2) {
+        assert!(pair[0].version < pair[1].version, "{pair:?}");
+    }
+    // 迁移中的表名必须使用模板，而不是写死数据库名
+    for m in MIGRATIONS {
+        assert!(!m.sql.contains("qihuo."), "{}", m.name);
+    }
+}
+
+#[test]
+fn applied_and_pending() -> Result<()> {
+    let applied = parse_applied("1\tczce\t2024-05-10 12:00:00\n")?;
+    assert_eq!(applied[0].version, 1);
+    assert_eq!(applied[0].applied_at, "2024-05-10 12:00:00");
+    let pending: Vec<_> = pending(&applied).map(|m| m.version).collect();
+    assert_eq!(pending[0], 2);
+    assert_eq!(pending.len(), MIGRATIONS.len() - 1);
+    assert!(parse_applied("x\tczce").is_err());
+
+    // 同一版本被并发记录两次
+    let applied = parse_applied(
+        "2\tdce\t2024-05-10 12:00:01\n1\tczce\t2024-05-10 12:00:00\n2\tdce\t2024-05-10 12:00:00\n",
+    )?;
+    let versions: Vec<_> = applied
+        .iter()
+        .map(|a| (a.version, &*a.applied_at))
+        .collect();
+    assert_eq!(
+        versions,
+        [(1, "2024-05-10 12:00:00"), (2, "2024-05-10 12:00:00")]
+    );
+    Ok(())
+}
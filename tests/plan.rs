@@ -0,0 +1,37 @@
+use commodity_exchange_zh::{czce, plan::Planner, util::init_test_log};
+use time::macros::date;
+
+#[test]
+fn dry_run_plan() {
+    init_test_log();
+    let mut planner = Planner::default();
+    let filter = czce::Filter {
+        from: Some(date!(2020 - 03 - 01)),
+        ..Default::default()
+    };
+    // 2019 年不在日期范围内
+    planner.czce(2019, &filter).unwrap();
+    planner.czce(2020, &filter).unwrap();
+    planner.dce_daily(date!(2024 - 05 - 10));
+    let plan = planner.finish();
+
+    let urls: Vec<_> = plan.downloads.iter().map(|d| d.url.as_str()).collect();
+    assert_eq!(
+        urls,
+        [
+            "http://www.czce.com.cn/cn/DFSStaticFiles/Future/2020/ALLFUTURES2020.zip",
+            "http://www.dce.com.cn/publicweb/quotesdata/exportDayQuotesChData.html"
+        ]
+    );
+    // 接口中的月份从 0 开始
+    assert!(plan.downloads[1]
+        .form
+        .contains(&("month".into(), "4".into())));
+    assert_eq!(plan.tables, ["qihuo.czce", "qihuo.dce"]);
+
+    let sql: Vec<_> = plan.sql.iter().map(|s| s.sql.as_str()).collect();
+    assert!(
+        sql.contains(&"ALTER TABLE qihuo.czce UPDATE dsp=Null WHERE dsp==0 AND year(date)==2020;")
+    );
+    assert!(sql.last().unwrap().contains("INSERT INTO qihuo.ce\n"));
+}
@@ -0,0 +1,166 @@
+use commodity_exchange_zh::util::prefetch::{host, Fetched, Scheduler};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+fn urls(hosts: &[&str], n: usize) -> Vec<String> {
+    (0..n)
+        .map(|i| format!("http://{}/{i}.zip", hosts[i % hosts.len()]))
+        .collect()
+}
+
+/// 等待 `f` 成立，最多 5 秒
+fn wait_until(f: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !f() {
+        assert!(started.elapsed() < Duration::from_secs(5), "等待超时");
+        sleep(Duration::from_millis(5));
+    }
+}
+
+fn body(fetched: Option<Fetched>) -> Vec<u8> {
+    match fetched {
+        Some(Fetched::Body(buf, _)) => buf,
+        other => panic!("{other:?}"),
+    }
+}
+
+#[test]
+fn url_host() {
+    assert_eq!(host("http://www.czce.com.cn/cn/a.zip"), "www.czce.com.cn");
+    assert_eq!(
+        host("https://www.dce.com.cn:8080?x=1"),
+        "www.dce.com.cn:8080"
+    );
+    assert_eq!(host("www.dce.com.cn/a"), "www.dce.com.cn");
+}
+
+/// 按原来的顺序开始下载，并按链接取出各自的结果
+#[test]
+fn ordered_take() {
+    let started = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&started);
+    let scheduler = Scheduler::new(
+        3,
+        1,
+        Box::new(move |url| {
+            log.lock().unwrap().push(url.to_owned());
+            Fetched::Body(url.as_bytes().to_vec(), Instant::now())
+        }),
+    );
+    // 同一主机最多 1 个同时进行，因此开始下载的顺序是确定的
+    let urls = urls(&["a.com"], 6);
+    let _prefetch = scheduler.start(urls.clone());
+    wait_until(|| started.lock().unwrap().len() == urls.len());
+    assert_eq!(*started.lock().unwrap(), urls);
+    for url in urls.iter().rev() {
+        assert_eq!(body(scheduler.take(url)), url.as_bytes());
+    }
+    assert_eq!(scheduler.outstanding(), 0);
+    assert!(scheduler.take(&urls[0]).is_none());
+}
+
+/// 同一主机同时进行的下载不超过 per_host 个
+#[test]
+fn per_host_limit() {
+    let running: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+    let max: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+    let (r, m) = (Arc::clone(&running), Arc::clone(&max));
+    let scheduler = Scheduler::new(
+        4,
+        2,
+        Box::new(move |url| {
+            let host = host(url).to_owned();
+            {
+                let mut running = r.lock().unwrap();
+                let n = running.entry(host.clone()).or_default();
+                *n += 1;
+                let max = &mut *m.lock().unwrap();
+                let max = max.entry(host.clone()).or_default();
+                *max = (*max).max(*n);
+            }
+            sleep(Duration::from_millis(20));
+            *r.lock().unwrap().get_mut(&host).unwrap() -= 1;
+            Fetched::NotFound
+        }),
+    );
+    let urls = urls(&["a.com", "b.com"], 12);
+    let _prefetch = scheduler.start(urls.clone());
+    for url in &urls {
+        // 尚未开始的下载返回 None，由调用方直接下载
+        if let Some(fetched) = scheduler.take(url) {
+            assert!(matches!(fetched, Fetched::NotFound), "{fetched:?}");
+        }
+    }
+    let max = max.lock().unwrap();
+    assert!(max.values().all(|&n| n <= 2), "{max:?}");
+}
+
+/// 已下载而尚未取用的结果不超过 2 * jobs 个
+#[test]
+fn window() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let c = Arc::clone(&calls);
+    let scheduler = Scheduler::new(
+        2,
+        4,
+        Box::new(move |url| {
+            c.fetch_add(1, Ordering::SeqCst);
+            Fetched::Body(url.as_bytes().to_vec(), Instant::now())
+        }),
+    );
+    let urls = urls(&["a.com"], 10);
+    let prefetch = scheduler.start(urls.clone());
+    wait_until(|| calls.load(Ordering::SeqCst) == 4);
+    sleep(Duration::from_millis(50));
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    assert_eq!(scheduler.outstanding(), 4);
+
+    // 取用一个之后才开始下一个
+    assert_eq!(body(scheduler.take(&urls[0])), urls[0].as_bytes());
+    wait_until(|| calls.load(Ordering::SeqCst) == 5);
+    assert_eq!(scheduler.outstanding(), 4);
+
+    // 丢弃时释放尚未取用的结果，并取消尚未开始的下载
+    drop(prefetch);
+    assert_eq!(scheduler.outstanding(), 0);
+    sleep(Duration::from_millis(50));
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+}
+
+/// 丢弃时正在进行的下载在完成后释放 outstanding
+#[test]
+fn cancel_running() {
+    let release = Arc::new(AtomicBool::new(false));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let (r, c) = (Arc::clone(&release), Arc::clone(&calls));
+    let scheduler = Scheduler::new(
+        2,
+        2,
+        Box::new(move |_| {
+            c.fetch_add(1, Ordering::SeqCst);
+            while !r.load(Ordering::SeqCst) {
+                sleep(Duration::from_millis(5));
+            }
+            Fetched::Failed("取消".into())
+        }),
+    );
+    let urls = urls(&["a.com"], 5);
+    let prefetch = scheduler.start(urls.clone());
+    wait_until(|| calls.load(Ordering::SeqCst) == 2);
+    assert_eq!(scheduler.outstanding(), 2);
+    drop(prefetch);
+    assert_eq!(scheduler.outstanding(), 2);
+
+    release.store(true, Ordering::SeqCst);
+    wait_until(|| scheduler.outstanding() == 0);
+    assert!(scheduler.take(&urls[0]).is_none());
+    sleep(Duration::from_millis(50));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,48 @@
+use commodity_exchange_zh::{
+    util::{
+        clickhouse, config::ClickHouse, manifest, save_csv, save_to_csv_and_clickhouse, set_config,
+        Config,
+    },
+    Result,
+};
+use std::{os::unix::fs::PermissionsExt, time::Instant};
+
+/// 录入在另一个线程中进行，录入和去重的行数仍然记入当前线程的下载记录
+#[test]
+fn inserted_recorded() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("ce-test-save-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    // 代替 clickhouse-client：不输出任何结果，录入时读完标准输入
+    let client = dir.join("clickhouse-client");
+    std::fs::write(
+        &client,
+        "#!/bin/sh\ncase \"$*\" in *INSERT*) cat > /dev/null ;; esac\n",
+    )?;
+    std::fs::set_permissions(&client, std::fs::Permissions::from_mode(0o755))?;
+    set_config(Config {
+        cache_dir: dir.clone(),
+        clickhouse: ClickHouse {
+            client: client.display().to_string(),
+            ..ClickHouse::default()
+        },
+        ..Config::default()
+    })?;
+
+    let _manifest = manifest::guard();
+    manifest::record("http://example.com/1.zip", b"1", Instant::now());
+    let csv = b"a,1\nb,2\nc,3\n";
+    save_to_csv_and_clickhouse(
+        || save_csv(csv, "ce-test-save"),
+        || clickhouse::insert_with_count_reported(&clickhouse::table("czce"), csv),
+    )?;
+    let entries = manifest::finish(Some("qihuo.czce"));
+    std::fs::remove_dir_all(&dir)?;
+    let entries = entries?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        (entries[0].inserted, entries[0].deduplicated),
+        (Some(3), Some(0))
+    );
+    assert_eq!(manifest::finished(), entries);
+    Ok(())
+}
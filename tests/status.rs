@@ -0,0 +1,52 @@
+use commodity_exchange_zh::{
+    dce::DownloadLinks,
+    status::{coverage, parse_stats, CacheFile},
+    Exchange,
+};
+use time::macros::{date, datetime};
+
+#[test]
+fn cache_files() {
+    let t = datetime!(2023-10-19 16:00 +8);
+    let f = CacheFile::parse("dce-2022-玉米.csv", t).unwrap();
+    assert_eq!(
+        (f.ce, f.year, f.name.as_deref()),
+        (Exchange::dce, 2022, Some("玉米"))
+    );
+    let f = CacheFile::parse("dce-daily-2024-05-06.csv", t).unwrap();
+    assert_eq!((f.year, f.name), (2024, None));
+    let f = CacheFile::parse("czce-ALLFUTURES2023.csv", t).unwrap();
+    assert_eq!((f.ce, f.year), (Exchange::czce, 2023));
+    assert!(CacheFile::parse("dce-dsp-2023.csv", t).is_none());
+    assert!(CacheFile::parse("dce-links.bincode", t).is_none());
+    assert!(CacheFile::parse("ce-main-MA.csv", t).is_none());
+}
+
+#[test]
+fn dce_keys_never_ingested() {
+    let stats = parse_stats(Exchange::dce, "2022\tc\t2022-01-04\t2022-12-30\t2400\n").unwrap();
+    let files = [
+        CacheFile::parse("dce-2022-玉米.csv", datetime!(2023-01-03 09:00 +8)).unwrap(),
+        CacheFile::parse("dce-2022-豆粕期权.csv", datetime!(2023-01-03 09:00 +8)).unwrap(),
+    ];
+    let links = DownloadLinks::new_static().unwrap();
+    let all = coverage(&[Exchange::dce], &stats, &files, &links);
+    let find = |product: &str| {
+        all.iter()
+            .find(|c| c.year == 2022 && c.product == product)
+            .unwrap()
+    };
+
+    let corn = find("C");
+    assert_eq!(corn.name.as_deref(), Some("玉米"));
+    assert_eq!((corn.rows, corn.last), (2400, Some(date!(2022 - 12 - 30))));
+    assert_eq!(corn.fetched, Some(datetime!(2023-01-03 09:00 +8)));
+    assert!(!corn.missing());
+    // 下载链接中的名称与日行情不同
+    assert!(find("A").missing());
+    assert_eq!(find("A").name.as_deref(), Some("黄大豆1号"));
+    // 期权以同名缓存文件为准
+    assert!(!find("豆粕期权").missing());
+    assert!(find("豆油期权").missing());
+    assert!(all.iter().all(|c| c.ce == Exchange::dce));
+}
@@ -0,0 +1,67 @@
+use commodity_exchange_zh::{
+    util::year::{Year, YearSpan},
+    Error,
+};
+
+#[test]
+fn parse_year() {
+    let y: Year = "2015, 2018..,..=2012".parse().unwrap();
+    assert_eq!(
+        y.0,
+        [
+            YearSpan::Single(2015),
+            YearSpan::Range {
+                start: Some(2018),
+                end: None,
+                inclusive: false
+            },
+            YearSpan::Range {
+                start: None,
+                end: Some(2012),
+                inclusive: true
+            },
+        ]
+    );
+    assert_eq!(y.0[1].to_string(), "2018..");
+    assert_eq!(y.0[2].to_string(), "..=2012");
+
+    for bad in [
+        "22",
+        "2018..=",
+        "2020..2020",
+        "2021..=2020",
+        "..0000",
+        "2018;2019",
+    ] {
+        assert!(bad.parse::<Year>().is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn resolve_year() {
+    let resolve = |s: &str| s.parse::<Year>().unwrap().resolve(2010..=2023);
+    assert_eq!(resolve("2020..2023").unwrap(), [2020, 2021, 2022]);
+    assert_eq!(resolve("2020..=2023").unwrap(), [2020, 2021, 2022, 2023]);
+    // 开放的一端取支持范围的端点
+    assert_eq!(resolve("2021..").unwrap(), [2021, 2022, 2023]);
+    assert_eq!(resolve("..2012").unwrap(), [2010, 2011]);
+    // 升序、去重
+    assert_eq!(
+        resolve("2022,2011..=2012,2012").unwrap(),
+        [2011, 2012, 2022]
+    );
+
+    for (s, year) in [
+        ("2009", 2009),
+        ("2024..", 2024),
+        ("..2010", 2009),
+        ("2022..=2024", 2024),
+    ] {
+        match resolve(s) {
+            Err(Error::UnsupportedYear { year: y, min, max }) => {
+                assert_eq!((y, min, max), (year, 2010, 2023), "{s}");
+            }
+            other => panic!("{s}: {other:?}"),
+        }
+    }
+}